@@ -0,0 +1,427 @@
+use crate::option;
+use crate::error;
+use crate::transcode;
+use crate::cli;
+
+use transcoding_rs as tc;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+use std::ffi;
+use std::fs;
+use std::path;
+use std::time;
+
+/// The formats of the archives whose entries are converted with --archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    Tar,
+}
+
+impl Format {
+    /// Tells the format of the archive from the format of the binary input.
+    /// Compressed tar archives are told after they are decompressed.
+    pub fn detect(binary_format: &str) -> Option<Self> {
+        return match binary_format {
+            "Zip" => Some(Format::Zip),
+            "tar" => Some(Format::Tar),
+            _ => None,
+        };
+    }
+
+    pub fn name(self: Self) -> &'static str {
+        return match self {
+            Format::Zip => "zip",
+            Format::Tar => "tar",
+        };
+    }
+
+    /// The name of the directory the entries are extracted into, which is the file name followed by ".d".
+    /// The extension is kept so that "data.zip" and "data.tar" are not extracted into the same directory.
+    /// The file name of a compressed archive is the one without the extension of the codec, like "data.tar".
+    pub fn directory_name(file_name: &ffi::OsStr) -> ffi::OsString {
+        let mut dir_name = file_name.to_owned();
+        dir_name.push(".d");
+        return dir_name;
+    }
+}
+
+/// Reports what --archive would do for --dry-run.
+pub fn plan(writer: &mut dyn io::Write, format: Format, destination: String, opt: &option::Opt,
+    relative_path: &path::PathBuf) -> Result<transcode::Outcome, error::Error> {
+    let action = if opt.archive == Some(option::ArchiveAction::Extract) { "extracted" } else { "rewritten" };
+    writer.write_fmt(format_args!("{}: {} archive -> entries {}, {}\n", relative_path.to_string_lossy(), format.name(), action, destination))
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+            _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the file".into() }
+        })?;
+    return Ok(transcode::Outcome::Converted);
+}
+
+/// Writes a new archive of the same format with the text entries converted.
+/// Binary entries, and the entries other than regular files, are written as they are with their metadata.
+pub fn rewrite(reader: &mut dyn io::Read, writer: &mut dyn io::Write, format: Format, src_encoding: Option<tc::Encoding>,
+    encoding: tc::Encoding, opt: &option::Opt, relative_path: &path::PathBuf) -> Result<transcode::Outcome, error::Error> {
+    let mut entries = Entries::new(src_encoding, encoding, opt, relative_path);
+    match format {
+        Format::Zip => rewrite_zip(reader, writer, &mut entries)?,
+        Format::Tar => rewrite_tar(reader, writer, &mut entries)?,
+    }
+    return entries.into_result();
+}
+
+/// Extracts the entries into the directory, converting the text entries.
+/// The permissions of the entries are kept, and the modification times of tar entries as well
+/// since the ones of zip entries have no time zone.
+pub fn extract(reader: &mut dyn io::Read, in_path: &path::PathBuf, dir_path: &path::PathBuf, format: Format,
+    src_encoding: Option<tc::Encoding>, encoding: tc::Encoding, opt: &option::Opt, relative_path: &path::PathBuf)
+    -> Result<transcode::Outcome, error::Error> {
+    fs::create_dir_all(dir_path)
+        .map_err(|e| error::Error::Io { source: e, path: dir_path.into(), message: "Error creating the directory".into() })?;
+    let mut entries = Entries::new(src_encoding, encoding, opt, relative_path);
+    match format {
+        Format::Zip => extract_zip(reader, in_path, dir_path, &mut entries)?,
+        Format::Tar => extract_tar(reader, in_path, dir_path, &mut entries)?,
+    }
+    return entries.into_result();
+}
+
+/// Converts the entries of an archive, and keeps the results of them.
+struct Entries<'a> {
+    src_encoding: Option<tc::Encoding>,
+    encoding: tc::Encoding,
+    opt: &'a option::Opt,
+    relative_path: &'a path::PathBuf,
+    converted: bool,
+    /// The first failure of the encoding detection, which is returned after the whole archive is written.
+    failure: Option<error::Error>,
+}
+
+impl<'a> Entries<'a> {
+    fn new(src_encoding: Option<tc::Encoding>, encoding: tc::Encoding, opt: &'a option::Opt, relative_path: &'a path::PathBuf) -> Self {
+        return Entries { src_encoding, encoding, opt, relative_path, converted: false, failure: None };
+    }
+
+    /// Reads the entry to convert it, except for binary entries, which are told by the head and whose rest is left unread
+    /// so that it's copied as it is. Returns the content read and whether it's the whole entry.
+    fn read(self: &Self, reader: &mut dyn io::Read) -> Result<(Vec<u8>, bool), error::Error> {
+        let mut content = Vec::new();
+        reader.take(transcode::SNIFF_SIZE).read_to_end(&mut content).map_err(|err| map_read_err(err, self.relative_path))?;
+        if self.binary_format(&content).is_some() {
+            return Ok((content, false));
+        }
+        reader.read_to_end(&mut content).map_err(|err| map_read_err(err, self.relative_path))?;
+        return Ok((content, true));
+    }
+
+    fn binary_format(self: &Self, head: &[u8]) -> Option<&'static str> {
+        // with -f, only magic numbers tell binary entries like the files
+        return tc::binary_format(head).filter(|format| self.src_encoding.is_none() || *format != tc::UNKNOWN_FORMAT);
+    }
+
+    /// Converts the content of the entry. Returns None if the content is kept as it is,
+    /// which is the case for binary entries and the entries whose encoding isn't detected.
+    fn convert(self: &mut Self, content: &[u8], entry_name: &str) -> Result<Option<Vec<u8>>, error::Error> {
+        let head = &content[..content.len().min(transcode::SNIFF_SIZE as usize)];
+        if self.binary_format(head).is_some() {
+            return Ok(None);
+        }
+        let entry_path = &path::PathBuf::from(format!("{}:{}", self.relative_path.to_string_lossy(), entry_name));
+        let mut output = Vec::with_capacity(content.len());
+        match transcode::transcode(&mut &content[..], &mut output, self.src_encoding, self.encoding, self.opt, entry_path) {
+            Ok(transcode::Outcome::Converted) => self.converted = true,
+            Ok(_) => {},
+            Err(err) if err.is_guess() => { // already reported, and the rest of the entries are still converted
+                if self.failure.is_none() {
+                    self.failure = Some(err);
+                }
+                return Ok(None);
+            },
+            Err(err) => return Err(err),
+        }
+        return Ok(if output == content { None } else { Some(output) });
+    }
+
+    fn into_result(self: Self) -> Result<transcode::Outcome, error::Error> {
+        return match (self.failure, self.converted) {
+            (Some(err), _) => Err(err),
+            (None, true) => Ok(transcode::Outcome::Converted),
+            (None, false) => Ok(transcode::Outcome::Unchanged),
+        };
+    }
+}
+
+fn rewrite_tar(reader: &mut dyn io::Read, writer: &mut dyn io::Write, entries: &mut Entries) -> Result<(), error::Error> {
+    let relative_path = entries.relative_path;
+    let mut archive = tar::Archive::new(reader);
+    let mut builder = tar::Builder::new(writer);
+    for entry in archive.entries().map_err(|err| map_read_err(err, relative_path))? {
+        let mut entry = entry.map_err(|err| map_read_err(err, relative_path))?;
+        let mut header = entry.header().clone();
+        let entry_path = entry.path().map_err(|err| map_read_err(err, relative_path))?.into_owned();
+        let entry_type = header.entry_type();
+        let mut extensions = pax_extensions(&mut entry).map_err(|err| map_read_err(err, relative_path))?;
+        // the paths in the extensions are kept with the header as it is, instead of adding GNU long name entries as well
+        let has_pax_path = extensions.iter().any(|(key, _)| key == "path" || key == "linkpath");
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let link_name = entry.link_name().map_err(|err| map_read_err(err, relative_path))?
+                .map(|l| l.into_owned()).unwrap_or_default();
+            let ret = builder.append_pax_extensions(extensions.iter().map(|(key, value)| (key.as_str(), &value[..])))
+                .and_then(|_| match has_pax_path {
+                    true => builder.append(&header, io::empty()),
+                    false => builder.append_link(&mut header, &entry_path, &link_name),
+                });
+            ret.map_err(|err| map_write_err(err, relative_path))?;
+            continue;
+        }
+        let (mut content, _) = match entry_type.is_file() {
+            true => entries.read(&mut entry)?,
+            false => (Vec::new(), false),
+        };
+        if entry_type.is_file() {
+            if let Some(output) = entries.convert(&content, &entry_path.to_string_lossy())? {
+                content = output;
+                header.set_size(content.len() as u64);
+                header.set_cksum();
+                extensions.retain(|(key, _)| key != "size");
+            }
+        }
+        let data = (&content[..]).chain(&mut entry); // the rest of the entry is left unread if it's kept as it is
+        let ret = builder.append_pax_extensions(extensions.iter().map(|(key, value)| (key.as_str(), &value[..])))
+            .and_then(|_| match has_pax_path {
+                true => builder.append(&header, data),
+                false => builder.append_data(&mut header, &entry_path, data),
+            });
+        ret.map_err(|err| map_write_err(err, relative_path))?;
+    }
+    builder.into_inner().map_err(|err| map_write_err(err, relative_path))?;
+    return Ok(());
+}
+
+fn rewrite_zip(reader: &mut dyn io::Read, writer: &mut dyn io::Write, entries: &mut Entries) -> Result<(), error::Error> {
+    let relative_path = entries.relative_path;
+    let mut archive = read_zip(reader, relative_path)?;
+    // zip files are written seeking back to the headers, so the new archive is built in a temporary file.
+    let output = tempfile::tempfile().map_err(|err| map_temp_err(err, relative_path))?;
+    let mut zip_writer = zip::ZipWriter::new(output);
+    for i in 0..archive.len() {
+        let (name, options, convertible) = {
+            let file = archive.by_index_raw(i).map_err(|err| map_read_err(err.into(), relative_path))?;
+            let mut options = zip::write::SimpleFileOptions::default()
+                .compression_method(file.compression())
+                .large_file(file.size() > u32::MAX as u64);
+            if let Some(modified) = file.last_modified() {
+                options = options.last_modified_time(modified);
+            }
+            if let Some(mode) = file.unix_mode() {
+                options = options.unix_permissions(mode);
+            }
+            let convertible = file.is_file() && ! file.is_symlink() && ! file.encrypted()
+                && matches!(file.compression(), zip::CompressionMethod::Stored | zip::CompressionMethod::Deflated);
+            (file.name().to_owned(), options, convertible)
+        };
+        let mut output = None;
+        if convertible {
+            let mut file = archive.by_index(i).map_err(|err| map_read_err(err.into(), relative_path))?;
+            let (content, whole) = entries.read(&mut file)?;
+            drop(file);
+            if whole {
+                output = entries.convert(&content, &name)?;
+            }
+        }
+        let ret = match output {
+            Some(output) => zip_writer.start_file(name, options).and_then(|_| zip_writer.write_all(&output).map_err(|err| err.into())),
+            None => archive.by_index_raw(i).and_then(|file| zip_writer.raw_copy_file(file)), // kept as it is with the metadata
+        };
+        ret.map_err(|err| map_write_err(err.into(), relative_path))?;
+    }
+    let mut output = zip_writer.finish().map_err(|err| map_write_err(err.into(), relative_path))?;
+    output.seek(io::SeekFrom::Start(0)).map_err(|err| map_temp_err(err, relative_path))?;
+    io::copy(&mut output, writer).map_err(|err| map_write_err(err, relative_path))?;
+    return Ok(());
+}
+
+fn extract_tar(reader: &mut dyn io::Read, in_path: &path::PathBuf, dir_path: &path::PathBuf, entries: &mut Entries)
+    -> Result<(), error::Error> {
+    let relative_path = entries.relative_path;
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().map_err(|err| map_read_err(err, relative_path))? {
+        let mut entry = entry.map_err(|err| map_read_err(err, relative_path))?;
+        let entry_path = entry.path().map_err(|err| map_read_err(err, relative_path))?.into_owned();
+        let out_path = &output_path(dir_path, &entry_path, relative_path)?;
+        let header = entry.header();
+        let entry_type = header.entry_type();
+        let (mode, modified) = (header.mode().ok(), header.mtime().ok());
+        if entry_type.is_dir() {
+            fs::create_dir_all(out_path)
+                .map_err(|e| error::Error::Io { source: e, path: out_path.into(), message: "Error creating the directory".into() })?;
+            continue;
+        }
+        create_parent(out_path)?;
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let link_name = entry.link_name().map_err(|err| map_read_err(err, relative_path))?
+                .map(|l| l.into_owned()).unwrap_or_default();
+            if entry_type.is_symlink() {
+                create_symlink(&link_name, out_path, entries.opt)?;
+            } else {
+                let target = &output_path(dir_path, &link_name, relative_path)?;
+                let _ = fs::remove_file(out_path);
+                fs::hard_link(target, out_path)
+                    .map_err(|e| error::Error::Io { source: e, path: out_path.into(), message: "Error creating the link".into() })?;
+            }
+            continue;
+        }
+        if ! entry_type.is_file() { // devices, FIFOs and global extension headers aren't extracted
+            continue;
+        }
+        let (content, whole) = entries.read(&mut entry)?;
+        let output = if whole { entries.convert(&content, &entry_path.to_string_lossy())? } else { None };
+        let modified = modified.map(|secs| time::UNIX_EPOCH + time::Duration::from_secs(secs));
+        let data = &mut output.as_deref().unwrap_or(&content[..]).chain(&mut entry); // the rest of a binary entry is copied
+        write_entry(in_path, out_path, data, mode, modified, entries.opt)?;
+    }
+    return Ok(());
+}
+
+fn extract_zip(reader: &mut dyn io::Read, in_path: &path::PathBuf, dir_path: &path::PathBuf, entries: &mut Entries)
+    -> Result<(), error::Error> {
+    let relative_path = entries.relative_path;
+    let mut archive = read_zip(reader, relative_path)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|err| map_read_err(err.into(), relative_path))?;
+        let entry_path = file.enclosed_name().ok_or_else(|| unsafe_path_err(file.name(), relative_path))?;
+        let out_path = &output_path(dir_path, &entry_path, relative_path)?;
+        if file.is_dir() {
+            fs::create_dir_all(out_path)
+                .map_err(|e| error::Error::Io { source: e, path: out_path.into(), message: "Error creating the directory".into() })?;
+            continue;
+        }
+        create_parent(out_path)?;
+        if file.is_symlink() { // the content is the target
+            let mut target = Vec::new();
+            file.read_to_end(&mut target).map_err(|err| map_read_err(err, relative_path))?;
+            create_symlink(&path::PathBuf::from(String::from_utf8_lossy(&target).as_ref()), out_path, entries.opt)?;
+            continue;
+        }
+        let (name, mode) = (file.name().to_owned(), file.unix_mode());
+        let (content, whole) = entries.read(&mut file)?;
+        let output = if whole { entries.convert(&content, &name)? } else { None };
+        let data = &mut output.as_deref().unwrap_or(&content[..]).chain(&mut file); // the rest of a binary entry is copied
+        write_entry(in_path, out_path, data, mode, None, entries.opt)?;
+    }
+    return Ok(());
+}
+
+/// Copies the zip file to a temporary file, since its central directory at the end needs to be read first.
+fn read_zip(reader: &mut dyn io::Read, relative_path: &path::PathBuf) -> Result<zip::ZipArchive<fs::File>, error::Error> {
+    let mut input = tempfile::tempfile().map_err(|err| map_temp_err(err, relative_path))?;
+    io::copy(reader, &mut input).map_err(|err| map_read_err(err, relative_path))?;
+    input.seek(io::SeekFrom::Start(0)).map_err(|err| map_temp_err(err, relative_path))?;
+    return zip::ZipArchive::new(input).map_err(|err| map_read_err(err.into(), relative_path));
+}
+
+/// Returns the PAX extended header records of the tar entry, which are lost unless they are written again.
+/// Global ones are entries by themselves, which are copied as they are.
+fn pax_extensions<R: io::Read>(entry: &mut tar::Entry<R>) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut records = Vec::new();
+    if entry.header().entry_type().is_pax_global_extensions() { // reading them would consume the content
+        return Ok(records);
+    }
+    if let Some(extensions) = entry.pax_extensions()? {
+        for extension in extensions {
+            let extension = extension?;
+            let key = extension.key().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            records.push((key.to_owned(), extension.value_bytes().to_owned()));
+        }
+    }
+    return Ok(records);
+}
+
+/// Joins the path of the entry to the directory, refusing the paths that would be written outside of the directory,
+/// including the ones through symbolic links extracted earlier.
+fn output_path(dir_path: &path::PathBuf, entry_path: &path::Path, relative_path: &path::PathBuf)
+    -> Result<path::PathBuf, error::Error> {
+    let mut out_path = dir_path.clone();
+    for component in entry_path.components() {
+        match component {
+            path::Component::Normal(name) => {
+                if fs::symlink_metadata(&out_path).map_or(false, |m| m.file_type().is_symlink()) {
+                    return Err(unsafe_path_err(&entry_path.to_string_lossy(), relative_path));
+                }
+                out_path.push(name);
+            },
+            path::Component::CurDir => {},
+            _ => return Err(unsafe_path_err(&entry_path.to_string_lossy(), relative_path)),
+        }
+    }
+    return Ok(out_path);
+}
+
+fn create_parent(out_path: &path::PathBuf) -> Result<(), error::Error> {
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| error::Error::Io { source: e, path: parent.into(), message: "Error creating the directory".into() })?;
+    }
+    return Ok(());
+}
+
+/// Writes the extracted entry like the other output files, which are kept with --no-clobber or backed up with --backup.
+fn write_entry(in_path: &path::PathBuf, out_path: &path::PathBuf, content: &mut dyn io::Read, mode: Option<u32>,
+    modified: Option<time::SystemTime>, opt: &option::Opt) -> Result<(), error::Error> {
+    let map_write_err = |err: io::Error| -> error::Error {
+        error::Error::Io { source: err, path: out_path.into(), message: "Error writing the file".into() }
+    };
+    let mut file = match cli::create_output(in_path, out_path, opt)? {
+        Some(file) => file,
+        None => return Ok(()),
+    };
+    io::copy(content, &mut file).map_err(map_write_err)?;
+    if let Some(modified) = modified {
+        file.set_times(fs::FileTimes::new().set_modified(modified)).map_err(map_write_err)?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(mode & 0o7777)).map_err(map_write_err)?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode; // the permissions are Unix ones
+    return Ok(());
+}
+
+fn create_symlink(target: &path::PathBuf, out_path: &path::PathBuf, opt: &option::Opt) -> Result<(), error::Error> {
+    if fs::symlink_metadata(out_path).is_ok() {
+        if opt.no_clobber {
+            return Ok(());
+        }
+        fs::remove_file(out_path)
+            .map_err(|e| error::Error::Io { source: e, path: out_path.into(), message: "Error removing the file".into() })?;
+    }
+    #[cfg(unix)]
+    let ret = std::os::unix::fs::symlink(target, out_path);
+    #[cfg(windows)]
+    let ret = std::os::windows::fs::symlink_file(target, out_path);
+    return ret.map_err(|e| error::Error::Io { source: e, path: out_path.into(), message: "Error creating the symbolic link".into() });
+}
+
+fn unsafe_path_err(entry_name: &str, relative_path: &path::PathBuf) -> error::Error {
+    let source = io::Error::new(io::ErrorKind::InvalidData, format!("Unsafe path of the entry: {}", entry_name));
+    return map_read_err(source, relative_path);
+}
+
+fn map_read_err(err: io::Error, relative_path: &path::PathBuf) -> error::Error {
+    return error::Error::Io { source: err, path: relative_path.into(), message: "Error reading the archive".into() };
+}
+
+fn map_temp_err(err: io::Error, relative_path: &path::PathBuf) -> error::Error {
+    return error::Error::Io { source: err, path: relative_path.into(), message: "Error using a temporary file".into() };
+}
+
+fn map_write_err(err: io::Error, relative_path: &path::PathBuf) -> error::Error {
+    return match err.kind() {
+        io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+        _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the archive".into() },
+    };
+}
@@ -1,12 +1,23 @@
 use crate::option;
 use crate::transcode;
 use crate::error;
+use crate::filter;
+use crate::summary;
+use crate::metadata;
+use crate::archive;
+use crate::compression;
 
-use encoding_rs as enc;
 use transcoding_rs as tc;
+use ignore::gitignore;
 use std::io;
 use std::fs;
 use std::path;
+use std::thread;
+use std::sync::atomic;
+use std::cell;
+use std::sync;
+use std::sync::mpsc;
+use std::collections;
 
 pub fn dispatch(opt: &option::Opt) -> Result<(), error::Error> {
     if opt.list {
@@ -22,22 +33,48 @@ pub fn dispatch(opt: &option::Opt) -> Result<(), error::Error> {
 
 fn run(opt: &option::Opt) -> Result<(), error::Error> {
 
-    let to_code = match enc::Encoding::for_label(opt.to_code.as_bytes()) {
+    let to_code = match tc::Encoding::for_label(opt.to_code.as_bytes()) {
         None => return Err(error::Error::Usage(format!("Invalid encoding: {}", opt.to_code))),
         Some(e) => e,
     };
+    let from_code = match &opt.from_code {
+        None => None,
+        Some(label) => match tc::Encoding::for_label(label.as_bytes()) {
+            None => return Err(error::Error::Usage(format!("Invalid encoding: {}", label))),
+            Some(e) => Some(e),
+        },
+    };
 
     let in_paths = &opt.paths;
+    if opt.in_place.is_some() && opt.plans_output() {
+        if in_paths.len() == 0 {
+            return Err(error::Error::Usage("Input files are required to convert files in place".into()));
+        }
+        if opt.output.is_some() {
+            return Err(error::Error::Usage("The output directory can't be specified when converting files in place".into()));
+        }
+    }
+    if opt.in_place.is_some() && opt.plans_output() && opt.compressed == option::CompressedAction::Decompress {
+        return Err(error::Error::Usage("Compressed files can't be decompressed when converting files in place".into()));
+    }
+    if opt.archive == Some(option::ArchiveAction::Extract) && opt.plans_output() && (in_paths.len() == 0 || opt.output.is_none()) {
+        return Err(error::Error::Usage("Input files and the output directory are required to extract archives".into()));
+    }
     let stdout = std::io::stdout();
     let mut stdout_lock;
     let (mut writer_opt, dir_opt): (Option<&mut dyn io::Write>,Option<&path::PathBuf>)  = {
-        if ! opt.show && in_paths.len() > 0 && opt.output.is_some() {
+        if opt.plans_output() && in_paths.len() > 0 && opt.output.is_some() {
             let out_path = opt.output.as_ref().unwrap();
-            if ! out_path.is_dir() {
-                fs::create_dir(&out_path)
-                    .map_err(|e| map_err(e, out_path, "Error creating the directory"))?;
+            if opt.dry_run { // the plan is written to stdout
+                stdout_lock = stdout.lock();
+                (Some(&mut stdout_lock), Some(out_path))
+            } else {
+                if ! out_path.is_dir() {
+                    fs::create_dir(&out_path)
+                        .map_err(|e| map_err(e, out_path, "Error creating the directory"))?;
+                }
+                (None, Some(out_path))
             }
-            (None, Some(out_path))
         } else {
             stdout_lock = stdout.lock();
             (Some(&mut stdout_lock), None)
@@ -45,35 +82,111 @@ fn run(opt: &option::Opt) -> Result<(), error::Error> {
     };
 
     if in_paths.len() == 0 {
-        let stdin = &mut std::io::stdin();
+        let relative_path = &"-".into();
+        let (mut reader, binary_format, codec) = compression::sniff(std::io::stdin(), opt, relative_path)?;
+        let binary_format = binary_format.filter(|format| ! is_declared_text(format, opt));
         let writer = writer_opt.unwrap();
-        return transcode::transcode(stdin, writer, to_code, &opt, &"-".into());
+        let archive_format = detect_archive(binary_format, opt);
+        let output_codec = output_codec(codec, binary_format, archive_format, opt);
+        if opt.dry_run {
+            let destination = describe_compression("-".into(), codec, output_codec);
+            if let Some(format) = archive_format {
+                return archive::plan(writer, format, destination, opt, relative_path).map(|_| ());
+            }
+            return transcode::plan(&mut reader, writer, binary_format, from_code, to_code, Some(destination), opt, relative_path).map(|_| ());
+        }
+        let outcome = compression::compress(writer, output_codec, relative_path, |writer| {
+            write_output(&mut reader, writer, binary_format, archive_format, from_code, to_code, opt, relative_path)
+        })?;
+        if let (transcode::Outcome::Skipped, Some(format), false) = (outcome, binary_format, opt.show) { // not dropped silently
+            eprintln!("-: Skipped the binary input ({}).", format);
+        }
+        return Ok(());
     } else {
+        let mut summary = summary::Summary::default();
         for i in 0..in_paths.len() {
             let in_path = &in_paths[i];
             if ! path::Path::exists(&in_paths[i]) {
                 let source = io::Error::new(io::ErrorKind::NotFound, "No such file or directory");
-                return Err(error::Error::Io { source, path: in_path.to_owned(), message: "Error opening the file".into() });
+                let err = error::Error::Io { source, path: in_path.to_owned(), message: "Error opening the file".into() };
+                summary.record(in_path, Err(err), opt)?;
             }
         }
-        for i in 0..in_paths.len() {
-            let in_path = &in_paths[i];
-            let in_path_can = &fs::canonicalize(&in_path)
-                .map_err(|e| map_err(e, in_path, "Error reading the path"))?;
-            traverse(&mut writer_opt, to_code, in_path, dir_opt, in_path, in_path_can, opt)?;
+        let filter = filter::Filter::new(opt)?;
+        let num_threads = if opt.jobs == 0 {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            opt.jobs
+        };
+        // `wait` waits for the files found so far to be converted.
+        let traverse_all = |visit: &mut dyn FnMut(Job, &mut summary::Summary) -> Result<(), error::Error>,
+            wait: &mut dyn FnMut(&mut summary::Summary) -> Result<(), error::Error>,
+            summary: &mut summary::Summary| -> Result<(), error::Error> {
+            for i in 0..in_paths.len() {
+                let in_path = &in_paths[i];
+                if ! path::Path::exists(in_path) { // already recorded
+                    continue;
+                }
+                let ret = fs::canonicalize(&in_path)
+                    .map_err(|e| map_err(e, in_path, "Error reading the path"))
+                    .and_then(|in_path_can| traverse(visit, summary, in_path, dir_opt, in_path, &in_path_can, &filter, &[], &[], opt));
+                if let Err(err) = ret {
+                    summary.record(in_path, Err(err), opt)?;
+                }
+                // errors on the contents don't stop the traversal of a directory, but the rest of the input paths
+                // are converted only with --check or --keep-going
+                if i + 1 < in_paths.len() && opt.check.is_none() && ! opt.keep_going {
+                    wait(summary)?;
+                    if summary.failures.len() > 0 {
+                        break;
+                    }
+                }
+            }
+            return Ok(());
+        };
+        if num_threads == 1 { // files are converted as they are found
+            let mut visit = |job: Job, summary: &mut summary::Summary| -> Result<(), error::Error> {
+                let ret = convert(&job, writer_opt.as_mut().map(|w| &mut **w as &mut dyn io::Write), from_code, to_code, opt);
+                return summary.record(&job.relative_path, ret, opt);
+            };
+            traverse_all(&mut visit, &mut |_| Ok(()), &mut summary)?;
+        } else {
+            convert_parallel(num_threads, &mut summary, writer_opt, from_code, to_code, opt, traverse_all)?;
         }
-        return Ok(());
+        if opt.keep_going {
+            summary.write(&mut io::stderr()).map_err(|e| map_err(e, &"-".into(), "Error writing the summary"))?;
+        }
+        return summary.into_result();
     }
 }
 
-fn traverse(writer_opt: &mut Option<&mut dyn io::Write>, to_code: &'static enc::Encoding,
-     in_path: &path::PathBuf, dir_opt: Option<&path::PathBuf>, in_root: &path::PathBuf, in_root_can: &path::PathBuf, opt: &option::Opt)
+/// A file to convert, which is found while traversing the input paths.
+struct Job {
+    in_path: path::PathBuf,
+    relative_path: path::PathBuf,
+    out_dir: Option<path::PathBuf>,
+    /// The canonical path of the input root if the file is a symbolic link to recreate in the output directory.
+    symlink_root: Option<path::PathBuf>,
+}
+
+fn traverse(visit: &mut dyn FnMut(Job, &mut summary::Summary) -> Result<(), error::Error>, summary: &mut summary::Summary,
+     in_path: &path::PathBuf, dir_opt: Option<&path::PathBuf>, in_root: &path::PathBuf, in_root_can: &path::PathBuf,
+     filter: &filter::Filter, ignores: &[gitignore::Gitignore], ancestors: &[metadata::FileId], opt: &option::Opt)
     -> Result<(), error::Error> {
     if in_path.is_dir() {
+        let dir_id = metadata::file_id(in_path);
+        if let Some(dir_id) = &dir_id {
+            if ancestors.contains(dir_id) { // a symbolic link to an ancestor would make the traversal endless
+                let source = io::Error::new(io::ErrorKind::Other, "The directory is its own ancestor");
+                summary.fail(in_path, map_err(source, in_path, "Error following a symbolic link loop"));
+                return Ok(());
+            }
+        }
+        let ancestors = [ancestors, dir_id.as_slice()].concat();
         let next_out_dir_opt= {
             if let Some(current_out_dir) = dir_opt {
                 let next_out_dir = current_out_dir.join(in_path.file_name().unwrap());
-                if ! next_out_dir.is_dir() {
+                if ! next_out_dir.is_dir() && ! opt.dry_run {
                     fs::create_dir(&next_out_dir)
                         .map_err(|e| map_err(e, &next_out_dir, "Error creating the directory"))?;
                 }
@@ -82,44 +195,430 @@ fn traverse(writer_opt: &mut Option<&mut dyn io::Write>, to_code: &'static enc::
                 None
             }
         };
-        let mut result: Result<(), error::Error> = Ok(());
+        let ignores = filter.ignores_in(in_path, ignores)?;
         let dir_ent = fs::read_dir(in_path)
             .map_err(|e| map_err(e, in_path, "Error reading the directory"))?;
         for child in dir_ent {
             let c = child
                 .map_err(|e| map_err(e, in_path, "Error reading the directory"))?;
             let child_path = &c.path();
-            let ret = traverse(writer_opt, to_code, child_path, next_out_dir_opt.as_ref(), in_root, in_root_can, opt);
-            if let Err(err) = ret {
-                if err.is_guess() {
-                    result = Err(err);
-                } else {
-                    return Err(err);
-                }
+            let relative_path = child_path.strip_prefix(in_root).unwrap_or(child_path);
+            if ! filter.accepts(child_path, relative_path, child_path.is_dir(), &ignores) {
+                continue;
+            }
+            let is_symlink = c.file_type().map_or(false, |t| t.is_symlink());
+            let ret = if is_symlink && opt.recreate_symlinks && next_out_dir_opt.is_some() {
+                let relative_path = job_relative_path(child_path, in_root, in_root_can);
+                let job = Job { in_path: child_path.into(), relative_path, out_dir: next_out_dir_opt.clone(),
+                    symlink_root: Some(in_root_can.into()) };
+                visit(job, summary)
+            } else if is_symlink && opt.no_follow_symlinks {
+                continue;
+            } else {
+                traverse(visit, summary, child_path, next_out_dir_opt.as_ref(), in_root, in_root_can, filter, &ignores, &ancestors, opt)
+            };
+            if let Err(err) = ret { // errors on the contents, or any errors with --keep-going, don't stop the traversal
+                summary.record(child_path, Err(err), opt)?;
             }
         }
-        return result;
+        return Ok(());
     } else {
-        let mut ofile;
-        let writer: &mut dyn io::Write = if let Some(dir_path) = dir_opt {
-            let out_path = &dir_path.join(in_path.file_name().unwrap());
-            ofile =fs::File::create(out_path)
-                .map_err(|e| map_err(e, out_path, "Error creating the file"))?;
-            &mut ofile
-        } else {
-            writer_opt.as_mut().unwrap()
+        let relative_path = job_relative_path(in_path, in_root, in_root_can);
+        return visit(Job { in_path: in_path.into(), relative_path, out_dir: dir_opt.cloned(), symlink_root: None }, summary);
+    }
+}
+
+/// Returns the path of a file found by the traversal as it's shown in the messages, which starts with the input root.
+fn job_relative_path(in_path: &path::PathBuf, in_root: &path::PathBuf, in_root_can: &path::PathBuf) -> path::PathBuf {
+    if let Ok(p) = in_path.strip_prefix(in_root_can) {
+        return in_root.join(p);
+    } else {
+        return in_path.into();
+    }
+}
+
+/// The number of files per thread that can be pending, i.e. found but not written yet, in the parallel conversion.
+const PENDING_FILES_PER_THREAD: usize = 4;
+
+type ConversionResult = (usize, Vec<u8>, Result<transcode::Outcome, error::Error>);
+
+/// Converts the files on `num_threads` threads as `traverse_all` finds them.
+/// The output to the writer is buffered for each file and written in the order the files were found.
+/// The traversal waits while too many files are pending, so that the buffered output doesn't pile up.
+/// Like the sequential conversion, the results are recorded in the summary, and the errors that stop the conversion,
+/// including the ones of the traversal, are returned after the output of the files found earlier is written.
+fn convert_parallel(num_threads: usize, summary: &mut summary::Summary, writer_opt: Option<&mut dyn io::Write>,
+    from_code: Option<tc::Encoding>, to_code: tc::Encoding, opt: &option::Opt,
+    traverse_all: impl FnOnce(&mut dyn FnMut(Job, &mut summary::Summary) -> Result<(), error::Error>,
+        &mut dyn FnMut(&mut summary::Summary) -> Result<(), error::Error>, &mut summary::Summary) -> Result<(), error::Error>)
+    -> Result<(), error::Error> {
+    let buffers_output = writer_opt.is_some();
+    let max_pending = num_threads * PENDING_FILES_PER_THREAD;
+    let aborted = atomic::AtomicBool::new(false);
+    let (job_sender, job_receiver) = mpsc::sync_channel::<(usize, Job)>(max_pending);
+    let job_receiver = sync::Mutex::new(job_receiver);
+    let (sender, receiver) = mpsc::channel::<ConversionResult>();
+    return thread::scope(|scope| {
+        for _ in 0..num_threads {
+            let sender = sender.clone();
+            let (job_receiver, aborted) = (&job_receiver, &aborted);
+            scope.spawn(move || loop {
+                let received = job_receiver.lock().unwrap().recv();
+                let (i, job) = match received {
+                    Ok(job) => job,
+                    Err(_) => break, // no more files
+                };
+                if aborted.load(atomic::Ordering::SeqCst) {
+                    break;
+                }
+                let mut buffer = Vec::new();
+                let writer: Option<&mut dyn io::Write> = if buffers_output { Some(&mut buffer) } else { None };
+                let ret = convert(&job, writer, from_code, to_code, opt);
+                if sender.send((i, buffer, ret)).is_err() { // the receiver stopped
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        let output = cell::RefCell::new(OrderedOutput {
+            receiver, writer_opt, aborted: &aborted, pending: collections::VecDeque::new(),
+            finished: collections::BTreeMap::new(), num_written: 0,
+        });
+        let mut num_found = 0;
+        let mut visit = |job: Job, summary: &mut summary::Summary| -> Result<(), error::Error> {
+            let mut output = output.borrow_mut();
+            while output.pending.len() >= max_pending {
+                output.receive(summary, opt)?;
+            }
+            output.pending.push_back(job.relative_path.clone());
+            job_sender.send((num_found, job)).expect("the threads should run until all files are sent");
+            num_found += 1;
+            return Ok(());
         };
-        let reader = &mut fs::File::open(in_path)
-            .map_err(|e| map_err(e, in_path, "Error creating the file"))?;
-        let relative_path = {
-            if let Ok(p) = in_path.strip_prefix(in_root_can) {
-                in_root.join(p)
-            } else {
-                in_path.into()
+        let mut wait = |summary: &mut summary::Summary| output.borrow_mut().receive_all(summary, opt);
+        let ret = traverse_all(&mut visit, &mut wait, summary);
+        drop(job_sender);
+        if aborted.load(atomic::Ordering::SeqCst) {
+            return ret;
+        }
+        output.borrow_mut().receive_all(summary, opt)?;
+        return ret;
+    });
+}
+
+/// The output of the parallel conversion, which writes the results received in the order the files were found.
+struct OrderedOutput<'a, 'w> {
+    receiver: mpsc::Receiver<ConversionResult>,
+    writer_opt: Option<&'w mut dyn io::Write>,
+    aborted: &'a atomic::AtomicBool,
+    /// The relative paths of the files found but not written yet.
+    pending: collections::VecDeque<path::PathBuf>,
+    /// The results received but not written yet, which wait for the files found earlier.
+    finished: collections::BTreeMap<usize, (Vec<u8>, Result<transcode::Outcome, error::Error>)>,
+    num_written: usize,
+}
+
+impl OrderedOutput<'_, '_> {
+    /// Receives the results of all the files found so far.
+    fn receive_all(self: &mut Self, summary: &mut summary::Summary, opt: &option::Opt) -> Result<(), error::Error> {
+        while ! self.pending.is_empty() {
+            self.receive(summary, opt)?;
+        }
+        return Ok(());
+    }
+
+    /// Receives a result, and writes the ones that are next in order.
+    fn receive(self: &mut Self, summary: &mut summary::Summary, opt: &option::Opt) -> Result<(), error::Error> {
+        let (i, buffer, ret) = self.receiver.recv().expect("the threads should run until all results are sent");
+        self.finished.insert(i, (buffer, ret));
+        while let Some((buffer, ret)) = self.finished.remove(&self.num_written) {
+            let relative_path = &self.pending.pop_front().unwrap();
+            self.num_written += 1;
+            let written = self.writer_opt.as_mut().map_or(Ok(()), |writer| writer.write_all(&buffer));
+            let ret = match written {
+                Ok(()) => ret,
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Err(error::Error::BrokenPipe), // Ignore broken pipe error. rust-lang/rust#46016
+                Err(err) => Err(map_err(err, relative_path, "Error writing the file")),
+            };
+            if let Err(err) = summary.record(relative_path, ret, opt) {
+                self.aborted.store(true, atomic::Ordering::SeqCst);
+                return Err(err);
             }
+        }
+        return Ok(());
+    }
+}
+
+fn convert(job: &Job, writer_opt: Option<&mut dyn io::Write>, from_code: Option<tc::Encoding>, to_code: tc::Encoding,
+    opt: &option::Opt) -> Result<transcode::Outcome, error::Error> {
+    let Job { in_path, relative_path, out_dir, symlink_root } = job;
+    if let Some(in_root_can) = symlink_root {
+        return recreate_symlink(in_path, relative_path, in_root_can, out_dir.as_ref().unwrap(), writer_opt, opt);
+    }
+    let reader = fs::File::open(in_path)
+        .map_err(|e| map_err(e, in_path, "Error opening the file"))?;
+    let (mut reader, binary_format, codec) = compression::sniff(reader, opt, relative_path)?;
+    let binary_format = binary_format.filter(|format| ! is_declared_text(format, opt));
+    let archive_format = detect_archive(binary_format, opt);
+    let output_codec = output_codec(codec, binary_format, archive_format, opt);
+    let file_name = in_path.file_name().unwrap();
+    let decompressed_name = codec.map_or_else(|| file_name.to_owned(), |c| c.strip_extension(file_name));
+    let out_name = if output_codec.is_some() { file_name } else { &decompressed_name };
+    // the entries are extracted into the directory named after the archive
+    let extract_path = match (archive_format, opt.archive, out_dir) {
+        (Some(_), Some(option::ArchiveAction::Extract), Some(dir_path)) => Some(dir_path.join(archive::Format::directory_name(&decompressed_name))),
+        _ => None,
+    };
+    if opt.dry_run {
+        let destination = match (&opt.in_place, out_dir, &extract_path) {
+            (_, _, Some(extract_path)) => format!("{}/", extract_path.to_string_lossy()),
+            (Some(_), _, None) => format!("{} (overwrite)", in_path.to_string_lossy()),
+            (None, Some(dir_path), None) => describe_output(in_path, &dir_path.join(out_name), opt),
+            (None, None, None) => "-".into(),
         };
-        return transcode::transcode(reader, writer, to_code, &opt, &relative_path);
+        let destination = describe_compression(destination, codec, output_codec);
+        let writer = writer_opt.unwrap();
+        if let Some(format) = archive_format {
+            return archive::plan(writer, format, destination, opt, relative_path);
+        }
+        return transcode::plan(&mut reader, writer, binary_format, from_code, to_code, Some(destination), opt, relative_path);
+    }
+    if let (Some(format), Some(extract_path)) = (archive_format, &extract_path) {
+        return archive::extract(&mut reader, in_path, extract_path, format, from_code, to_code, opt, relative_path);
+    }
+    let in_place = opt.in_place.is_some() && opt.writes_output();
+    if let (Some(format), None) = (binary_format, archive_format) {
+        if in_place || (out_dir.is_some() && opt.binary != option::BinaryAction::Copy) { // binary files are left untouched
+            return transcode::binary(&mut reader, &mut io::sink(), format, opt, relative_path);
+        }
+    }
+    if let (Some(suffix), true) = (&opt.in_place, in_place) {
+        return transcode_in_place(in_path, suffix, opt, |file| {
+            compression::compress(file, output_codec, relative_path, |writer| {
+                write_output(&mut reader, writer, binary_format, archive_format, from_code, to_code, opt, relative_path)
+            })
+        });
+    }
+    let out_path = out_dir.as_ref().map(|dir_path| dir_path.join(out_name));
+    let mut ofile = None;
+    let writer: &mut dyn io::Write = if let Some(out_path) = &out_path {
+        ofile = create_output(in_path, out_path, opt)?;
+        match ofile.as_mut() {
+            Some(file) => file,
+            None => return Ok(transcode::Outcome::Skipped), // kept with --no-clobber
+        }
+    } else {
+        writer_opt.unwrap()
+    };
+    let outcome = compression::compress(writer, output_codec, relative_path, |writer| {
+        write_output(&mut reader, writer, binary_format, archive_format, from_code, to_code, opt, relative_path)
+    })?;
+    if let (Some(file), Some(out_path)) = (&ofile, &out_path) {
+        metadata::preserve(in_path, file, out_path, opt.preserve())?;
+    }
+    return Ok(outcome);
+}
+
+/// Writes the converted archive, the binary input according to --binary, or the converted texts.
+fn write_output(reader: &mut dyn io::Read, writer: &mut dyn io::Write, binary_format: Option<&'static str>,
+    archive_format: Option<archive::Format>, from_code: Option<tc::Encoding>, to_code: tc::Encoding, opt: &option::Opt,
+    relative_path: &path::PathBuf) -> Result<transcode::Outcome, error::Error> {
+    if let Some(format) = archive_format {
+        return archive::rewrite(reader, writer, format, from_code, to_code, opt, relative_path);
+    } else if let Some(format) = binary_format {
+        return transcode::binary(reader, writer, format, opt, relative_path);
+    }
+    return transcode::transcode(reader, writer, from_code, to_code, &opt, relative_path);
+}
+
+/// Creates a symbolic link in the output directory to the same target as the input symbolic link,
+/// or describes it with --dry-run.
+fn recreate_symlink(in_path: &path::PathBuf, relative_path: &path::PathBuf, in_root_can: &path::PathBuf, out_dir: &path::PathBuf,
+    writer_opt: Option<&mut dyn io::Write>, opt: &option::Opt) -> Result<transcode::Outcome, error::Error> {
+    let target = fs::read_link(in_path)
+        .map_err(|e| map_err(e, in_path, "Error reading the symbolic link"))?;
+    let target = output_symlink_target(in_path, target, in_root_can);
+    let out_path = &out_dir.join(in_path.file_name().unwrap());
+    let exists = fs::symlink_metadata(out_path).is_ok();
+    if opt.dry_run {
+        let state = if ! exists { "create" } else if opt.no_clobber { "exists, kept" } else { "overwrite" };
+        let writer = writer_opt.unwrap();
+        writer.write_fmt(format_args!("{}: symbolic link to {}, {} ({})\n",
+            relative_path.to_string_lossy(), target.to_string_lossy(), out_path.to_string_lossy(), state))
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+                _ => map_err(e, relative_path, "Error writing the file"),
+            })?;
+        return Ok(transcode::Outcome::Unchanged);
+    }
+    if exists {
+        if opt.no_clobber {
+            return Ok(transcode::Outcome::Skipped);
+        }
+        fs::remove_file(out_path)
+            .map_err(|e| map_err(e, out_path, "Error removing the file"))?;
+    }
+    #[cfg(unix)]
+    let ret = std::os::unix::fs::symlink(&target, out_path);
+    #[cfg(windows)]
+    let ret = if in_path.is_dir() {
+        std::os::windows::fs::symlink_dir(&target, out_path)
+    } else {
+        std::os::windows::fs::symlink_file(&target, out_path)
+    };
+    ret.map_err(|e| map_err(e, out_path, "Error creating the symbolic link"))?;
+    return Ok(transcode::Outcome::Unchanged);
+}
+
+/// Returns the target of the symbolic link recreated in the output directory.
+/// An absolute target in the input root is rewritten to the relative path to the same file in the output root,
+/// and relative targets are kept as they are, since the output directory has the same structure as the input root.
+fn output_symlink_target(in_path: &path::PathBuf, target: path::PathBuf, in_root_can: &path::PathBuf) -> path::PathBuf {
+    let link_dir = match (target.is_absolute(), in_path.parent().and_then(|p| fs::canonicalize(p).ok())) {
+        (true, Some(link_dir)) => link_dir,
+        _ => return target,
+    };
+    let (target_in_root, link_dir_in_root) = match (target.strip_prefix(in_root_can), link_dir.strip_prefix(in_root_can)) {
+        (Ok(target_in_root), Ok(link_dir_in_root)) => (target_in_root, link_dir_in_root),
+        _ => return target,
+    };
+    let mut rewritten = path::PathBuf::new();
+    for _ in link_dir_in_root.components() {
+        rewritten.push("..");
+    }
+    rewritten.push(target_in_root);
+    if rewritten.as_os_str().is_empty() { // the input root itself
+        rewritten.push(".");
+    }
+    return rewritten;
+}
+
+/// Replaces the file with the output written by `write` to a temporary file.
+fn transcode_in_place(in_path: &path::PathBuf, suffix: &Option<String>, opt: &option::Opt,
+    write: impl FnOnce(&mut fs::File) -> Result<transcode::Outcome, error::Error>) -> Result<transcode::Outcome, error::Error> {
+    // the file is replaced by a new one, so the target of a symbolic link is replaced instead of the link.
+    let resolved_path;
+    let in_path = if fs::symlink_metadata(in_path).map_or(false, |m| m.file_type().is_symlink()) {
+        resolved_path = fs::canonicalize(in_path).map_err(|e| map_err(e, in_path, "Error following the symbolic link"))?;
+        &resolved_path
+    } else {
+        in_path
+    };
+    let backup_path = suffix.as_ref().filter(|s| s.len() > 0).map(|suffix| with_suffix(in_path, suffix));
+    let backup_exists = backup_path.as_ref().map_or(false, |p| fs::symlink_metadata(p).is_ok());
+    if let (Some(backup_path), true, false) = (&backup_path, backup_exists, opt.force) {
+        let source = io::Error::new(io::ErrorKind::AlreadyExists, "The backup file already exists (use --force to overwrite it)");
+        return Err(map_err(source, backup_path, "Error creating the backup file"));
+    }
+    let dir_path = match in_path.parent() {
+        Some(p) if p.as_os_str().len() > 0 => p,
+        _ => path::Path::new("."),
+    };
+    let mut temp_file = tempfile::Builder::new().prefix(".aconv").tempfile_in(dir_path)
+        .map_err(|e| map_err(e, &dir_path.into(), "Error creating a temporary file"))?;
+    // the temporary file is removed when dropped, so the original file is left untouched on errors.
+    let outcome = write(temp_file.as_file_mut())?;
+
+    let preserve = metadata::Preserve { mode: true, timestamps: true, xattr: opt.preserve().xattr };
+    metadata::preserve(in_path, temp_file.as_file(), &temp_file.path().into(), preserve)?;
+
+    if let Some(backup_path) = &backup_path {
+        if backup_exists {
+            fs::remove_file(backup_path).map_err(|e| map_err(e, backup_path, "Error removing the old backup file"))?;
+        }
+        fs::hard_link(in_path, backup_path)
+            .or_else(|_| fs::copy(in_path, backup_path).map(|_| ())) // hard links may not be supported
+            .map_err(|e| map_err(e, backup_path, "Error creating the backup file"))?;
+    }
+    temp_file.persist(in_path)
+        .map_err(|e| map_err(e.error, in_path, "Error replacing the file"))?;
+    return Ok(outcome);
+}
+
+/// Creates the output file, which is kept with --no-clobber, renamed with --backup,
+/// or removed to be recreated with --force if it already exists.
+/// Returns None if the file is kept.
+pub fn create_output(in_path: &path::PathBuf, out_path: &path::PathBuf, opt: &option::Opt)
+    -> Result<Option<fs::File>, error::Error> {
+    if out_path.exists() {
+        if metadata::is_same_file(in_path, out_path) { // creating it would truncate the input before it's read
+            let source = io::Error::new(io::ErrorKind::InvalidInput, "The output file is the input file itself");
+            return Err(map_err(source, out_path, "Error creating the file"));
+        }
+        if opt.no_clobber {
+            return Ok(None);
+        }
+        if let Some(suffix) = &opt.backup {
+            let backup_path = &with_suffix(out_path, suffix.as_deref().filter(|s| s.len() > 0).unwrap_or("~"));
+            fs::rename(out_path, backup_path)
+                .map_err(|e| map_err(e, backup_path, "Error creating the backup file"))?;
+        }
     }
+    let file = match fs::File::create(out_path) {
+        Err(e) if opt.force && e.kind() == io::ErrorKind::PermissionDenied => {
+            fs::remove_file(out_path).and_then(|_| fs::File::create(out_path))
+        },
+        ret => ret,
+    };
+    return file.map(Some).map_err(|e| map_err(e, out_path, "Error creating the file"));
+}
+
+/// Tells if the binary input is still converted as texts, which is the case with -f unless the input has a magic number,
+/// since too many non-text bytes may be a part of the texts in the encoding given.
+fn is_declared_text(binary_format: &str, opt: &option::Opt) -> bool {
+    return opt.from_code.is_some() && binary_format == tc::UNKNOWN_FORMAT;
+}
+
+/// Tells the format of the archive whose entries are converted with --archive, if the binary input is one.
+fn detect_archive(binary_format: Option<&'static str>, opt: &option::Opt) -> Option<archive::Format> {
+    return match (opt.archive, binary_format) {
+        (Some(_), Some(format)) if opt.plans_output() => archive::Format::detect(format),
+        _ => None,
+    };
+}
+
+/// The codec the output is compressed with, which is the one of the input with --compressed=recompress
+/// unless the input is a binary file that isn't output.
+fn output_codec(codec: Option<compression::Codec>, binary_format: Option<&'static str>, archive_format: Option<archive::Format>,
+    opt: &option::Opt) -> Option<compression::Codec> {
+    if opt.compressed != option::CompressedAction::Recompress {
+        return None;
+    }
+    if binary_format.is_some() && archive_format.is_none() && opt.binary != option::BinaryAction::Copy {
+        return None;
+    }
+    return codec;
+}
+
+/// Appends how the output is compressed to the destination for --dry-run.
+fn describe_compression(destination: String, codec: Option<compression::Codec>, output_codec: Option<compression::Codec>) -> String {
+    return match (codec, output_codec) {
+        (_, Some(output_codec)) => format!("{}, {}", destination, output_codec.name()),
+        (Some(codec), None) => format!("{}, decompressed from {}", destination, codec.name()),
+        (None, None) => destination,
+    };
+}
+
+/// Describes what would happen to the output file for --dry-run.
+fn describe_output(in_path: &path::PathBuf, out_path: &path::PathBuf, opt: &option::Opt) -> String {
+    let out_path_str = out_path.to_string_lossy();
+    if ! out_path.exists() {
+        return format!("{} (create)", out_path_str);
+    } else if metadata::is_same_file(in_path, out_path) {
+        return format!("{} (the input file itself, error)", out_path_str);
+    } else if opt.no_clobber {
+        return format!("{} (exists, kept)", out_path_str);
+    } else if let Some(suffix) = &opt.backup {
+        let backup_path = with_suffix(out_path, suffix.as_deref().filter(|s| s.len() > 0).unwrap_or("~"));
+        return format!("{} (overwrite, backup to {})", out_path_str, backup_path.to_string_lossy());
+    }
+    return format!("{} (overwrite)", out_path_str);
+}
+
+fn with_suffix(file_path: &path::PathBuf, suffix: &str) -> path::PathBuf {
+    let mut file_name = file_path.file_name().unwrap().to_owned();
+    file_name.push(suffix);
+    return file_path.with_file_name(file_name);
 }
 
 fn map_err(e: io::Error, path: &path::PathBuf, msg: &str) -> error::Error {
@@ -0,0 +1,116 @@
+use crate::option;
+use crate::error;
+use crate::transcode;
+
+use std::io;
+use std::ffi;
+use std::path;
+
+/// The compression formats whose inputs are decompressed to be converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Codec {
+    /// Tells the codec from the format of the binary input.
+    pub fn detect(binary_format: &str) -> Option<Self> {
+        return match binary_format {
+            "gzip" => Some(Codec::Gzip),
+            "bzip2" => Some(Codec::Bzip2),
+            "XZ" => Some(Codec::Xz),
+            "Zstandard" => Some(Codec::Zstd),
+            _ => None,
+        };
+    }
+
+    pub fn name(self: Self) -> &'static str {
+        return match self {
+            Codec::Gzip => "gzip",
+            Codec::Bzip2 => "bzip2",
+            Codec::Xz => "xz",
+            Codec::Zstd => "zstd",
+        };
+    }
+
+    /// The file name without the extension of the codec, which is the name of the decompressed file.
+    /// "tgz" is replaced with "tar".
+    pub fn strip_extension(self: Self, file_name: &ffi::OsStr) -> ffi::OsString {
+        let extension = match self {
+            Codec::Gzip => "gz",
+            Codec::Bzip2 => "bz2",
+            Codec::Xz => "xz",
+            Codec::Zstd => "zst",
+        };
+        let mut name = path::PathBuf::from(file_name);
+        match name.extension() {
+            Some(e) if e.eq_ignore_ascii_case(extension) => { name.set_extension(""); },
+            Some(e) if e.eq_ignore_ascii_case("tgz") && self == Codec::Gzip => { name.set_extension("tar"); },
+            _ => {},
+        }
+        return name.into_os_string();
+    }
+
+    /// Wraps the reader with the decoder of the codec. Concatenated streams are decompressed to the end.
+    pub fn decoder<'a, R: io::Read + 'a>(self: Self, reader: R) -> io::Result<Box<dyn io::Read + 'a>> {
+        return Ok(match self {
+            Codec::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Codec::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+            Codec::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            Codec::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        });
+    }
+}
+
+/// Reads the head of the input like `transcode::sniff`, decompressing the input first if it's compressed
+/// unless --compressed is binary. Returns the codec of the input as well, which is None if it's not decompressed.
+pub fn sniff<'a, R: io::Read + 'a>(reader: R, opt: &option::Opt, relative_path: &path::PathBuf)
+    -> Result<(io::Chain<io::Cursor<Vec<u8>>, Box<dyn io::Read + 'a>>, Option<&'static str>, Option<Codec>), error::Error> {
+    let (reader, binary_format) = transcode::sniff(reader, relative_path)?;
+    let codec = binary_format.and_then(Codec::detect).filter(|_| opt.compressed != option::CompressedAction::Binary);
+    if let Some(codec) = codec {
+        let decoder = codec.decoder(reader)
+            .map_err(|err| error::Error::Io { source: err, path: relative_path.into(), message: "Error reading the file".into() })?;
+        let (reader, binary_format) = transcode::sniff(decoder, relative_path)?;
+        return Ok((reader, binary_format, Some(codec)));
+    }
+    let (head, rest) = reader.into_inner();
+    return Ok((io::Read::chain(head, Box::new(rest)), binary_format, None));
+}
+
+/// Writes the output with `write` through the encoder of the codec, or as it is if the codec is None.
+/// The encoder is finished even if `write` fails, since the input may have been written as-is.
+pub fn compress(writer: &mut dyn io::Write, codec: Option<Codec>, relative_path: &path::PathBuf,
+    write: impl FnOnce(&mut dyn io::Write) -> Result<transcode::Outcome, error::Error>) -> Result<transcode::Outcome, error::Error> {
+    let map_write_err = |err: io::Error| -> error::Error {
+        match err.kind() {
+            io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+            _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the file".into() }
+        }
+    };
+    let (ret, finished) = match codec {
+        None => return write(writer),
+        Some(Codec::Gzip) => {
+            let mut encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+            (write(&mut encoder), encoder.finish().map(|_| ()))
+        },
+        Some(Codec::Bzip2) => {
+            let mut encoder = bzip2::write::BzEncoder::new(writer, bzip2::Compression::default());
+            (write(&mut encoder), encoder.finish().map(|_| ()))
+        },
+        Some(Codec::Xz) => {
+            let mut encoder = xz2::write::XzEncoder::new(writer, 6);
+            (write(&mut encoder), encoder.finish().map(|_| ()))
+        },
+        Some(Codec::Zstd) => {
+            let mut encoder = zstd::stream::write::Encoder::new(writer, 0).map_err(map_write_err)?;
+            (write(&mut encoder), encoder.finish().map(|_| ()))
+        },
+    };
+    let outcome = ret?;
+    finished.map_err(map_write_err)?;
+    return Ok(outcome);
+}
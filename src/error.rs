@@ -3,6 +3,14 @@ use std::io;
 use std::path;
 use std::fmt;
 use exitcode;
+use transcoding_rs as tc;
+
+/// Exit code for malformed byte sequences or unmappable characters found in the strict mode.
+/// `exitcode` has no dedicated code for this, so the same value as EILSEQ on Linux is used.
+pub const ILSEQ: exitcode::ExitCode = 84;
+
+/// Exit code for files found by --check, which is the same as `diff` and `cmp` return for differences.
+pub const CHECK_FAILED: exitcode::ExitCode = 1;
 
 #[derive(Debug)]
 pub enum Error {
@@ -10,6 +18,11 @@ pub enum Error {
     BrokenPipe,
     Guess(String),
     Usage(String),
+    Transcode{source: tc::TranscodeError, path: path::PathBuf},
+    Check(String),
+    Binary(String),
+    /// An error that has already been reported, which is the most severe one of the errors with --keep-going.
+    Reported(Box<Error>),
 }
 
 impl Error {
@@ -19,6 +32,34 @@ impl Error {
         }
         return false;
     }
+    pub fn is_check(self: &Self) -> bool {
+        if let Error::Check(_) = self {
+            return true;
+        }
+        return false;
+    }
+    pub fn is_binary(self: &Self) -> bool {
+        if let Error::Binary(_) = self {
+            return true;
+        }
+        return false;
+    }
+    /// Whether the error has already been reported to stderr when it occurred.
+    pub fn is_reported(self: &Self) -> bool {
+        return self.is_guess() || self.is_check() || self.is_binary() || matches!(self, Error::Reported(_));
+    }
+    /// How severe the error is, which decides the exit code when multiple errors occurred.
+    pub fn severity(self: &Self) -> u8 {
+        match self {
+            Error::BrokenPipe => 0,
+            Error::Check(_) => 1,
+            Error::Guess(_)|Error::Binary(_) => 2,
+            Error::Transcode{..} => 3,
+            Error::Io{..} => 4,
+            Error::Usage(_) => 5,
+            Error::Reported(err) => err.severity(),
+        }
+    }
     pub fn is_broken_pipe(self: &Self) -> bool {
         if let Error::BrokenPipe = self {
             return true;
@@ -31,6 +72,10 @@ impl Error {
             Error::BrokenPipe => exitcode::OK, // Ignore broken pipe error. rust-lang/rust#46016
             Error::Guess(_) => exitcode::DATAERR,
             Error::Usage(_) => exitcode::USAGE,
+            Error::Transcode{..} => ILSEQ,
+            Error::Check(_) => CHECK_FAILED,
+            Error::Binary(_) => exitcode::DATAERR,
+            Error::Reported(err) => err.error_code(),
         }
     }
 }
@@ -40,7 +85,9 @@ impl fmt::Display for Error {
         match self {
             Error::Io{source, path, message} => write!(f, "{}: {}. Cause: {}", path.to_string_lossy(), message, source),
             Error::BrokenPipe => write!(f, ""), // Ignore broken pipe error. rust-lang/rust#46016
-            Error::Guess(message)|Error::Usage(message) => write!(f, "{}", message),
+            Error::Guess(message)|Error::Usage(message)|Error::Check(message)|Error::Binary(message) => write!(f, "{}", message),
+            Error::Transcode{source, path} => write!(f, "{}: {}.", path.to_string_lossy(), source),
+            Error::Reported(err) => write!(f, "{}", err),
         }
     }
 }
@@ -0,0 +1,108 @@
+use crate::option;
+use crate::error;
+
+use globset;
+use ignore::gitignore;
+use std::io;
+use std::fs;
+use std::path;
+
+/// The names of the ignore files honored with --use-ignore-files.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Decides which entries found while traversing directories are processed.
+/// Paths given as arguments are always processed.
+#[derive(Debug)]
+pub struct Filter {
+    include: Option<globset::GlobSet>,
+    exclude: globset::GlobSet,
+    skip_hidden: bool,
+    use_ignore_files: bool,
+    /// The canonical path of the output directory, which is skipped if it's in the input directories.
+    output_dir: Option<path::PathBuf>,
+}
+
+impl Filter {
+    pub fn new(opt: &option::Opt) -> Result<Self, error::Error> {
+        let include = if opt.include.len() > 0 {
+            Some(build_glob_set(&opt.include)?)
+        } else {
+            None
+        };
+        let exclude = build_glob_set(&opt.exclude)?;
+        let output_dir = opt.output.as_ref().and_then(|p| fs::canonicalize(p).ok());
+        return Ok(Filter { include, exclude, skip_hidden: opt.skip_hidden, use_ignore_files: opt.use_ignore_files, output_dir });
+    }
+
+    /// Returns the ignore files in the directory appended to the ones of its ancestors.
+    pub fn ignores_in(self: &Self, dir_path: &path::PathBuf, ancestors: &[gitignore::Gitignore])
+        -> Result<Vec<gitignore::Gitignore>, error::Error> {
+        let mut ignores = ancestors.to_vec();
+        if ! self.use_ignore_files {
+            return Ok(ignores);
+        }
+        let mut builder = gitignore::GitignoreBuilder::new(dir_path);
+        let mut found = false;
+        for name in IGNORE_FILES.iter() {
+            let ignore_path = dir_path.join(name);
+            if ignore_path.is_file() {
+                found = true;
+                if let Some(err) = builder.add(&ignore_path) {
+                    return Err(map_ignore_err(err, &ignore_path));
+                }
+            }
+        }
+        if found {
+            ignores.push(builder.build().map_err(|err| map_ignore_err(err, dir_path))?);
+        }
+        return Ok(ignores);
+    }
+
+    /// Whether the entry found while traversing is processed.
+    /// Globs are matched against both the file name and the path relative to the argument,
+    /// and --include applies only to files so that directories are always descended.
+    pub fn accepts(self: &Self, entry_path: &path::Path, relative_path: &path::Path, is_dir: bool,
+        ignores: &[gitignore::Gitignore]) -> bool {
+        let file_name = match entry_path.file_name() {
+            Some(name) => path::Path::new(name),
+            None => return true,
+        };
+        if self.skip_hidden && file_name.to_string_lossy().starts_with('.') {
+            return false;
+        }
+        if let (Some(output_dir), true) = (&self.output_dir, is_dir) {
+            if fs::canonicalize(entry_path).map_or(false, |p| &p == output_dir) {
+                return false;
+            }
+        }
+        if self.exclude.is_match(file_name) || self.exclude.is_match(relative_path) {
+            return false;
+        }
+        if let (Some(include), false) = (&self.include, is_dir) {
+            if ! include.is_match(file_name) && ! include.is_match(relative_path) {
+                return false;
+            }
+        }
+        for ignore in ignores.iter().rev() { // the nearest ignore file takes precedence
+            match ignore.matched(entry_path, is_dir) {
+                ignore::Match::Ignore(_) => return false,
+                ignore::Match::Whitelist(_) => return true,
+                ignore::Match::None => {},
+            }
+        }
+        return true;
+    }
+}
+
+fn build_glob_set(globs: &[globset::Glob]) -> Result<globset::GlobSet, error::Error> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(glob.clone());
+    }
+    return builder.build().map_err(|err| error::Error::Usage(format!("Invalid glob: {}", err)));
+}
+
+fn map_ignore_err(err: ignore::Error, path: &path::PathBuf) -> error::Error {
+    let source = io::Error::new(io::ErrorKind::InvalidData, err.to_string());
+    return error::Error::Io { source, path: path.into(), message: "Error reading the ignore file".into() };
+}
@@ -2,4 +2,10 @@ pub mod option;
 pub mod error;
 pub mod cli;
 pub mod transcode;
+pub mod report;
+pub mod filter;
+pub mod summary;
+pub mod metadata;
+pub mod archive;
+pub mod compression;
 
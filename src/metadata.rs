@@ -0,0 +1,79 @@
+use crate::error;
+
+use std::io;
+use std::fs;
+use std::path;
+
+/// The metadata copied from the input file to the output file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Preserve {
+    pub mode: bool,
+    pub timestamps: bool,
+    pub xattr: bool,
+}
+
+/// Copies the metadata of the input file to the output file, which needs to be written already
+/// since writing it changes the modification time.
+pub fn preserve(in_path: &path::PathBuf, out_file: &fs::File, out_path: &path::PathBuf, preserve: Preserve)
+    -> Result<(), error::Error> {
+    let map_read_err = |err: io::Error| -> error::Error {
+        error::Error::Io { source: err, path: in_path.into(), message: "Error reading the metadata".into() }
+    };
+    let map_write_err = |err: io::Error| -> error::Error {
+        error::Error::Io { source: err, path: out_path.into(), message: "Error setting the metadata".into() }
+    };
+    let metadata = fs::metadata(in_path).map_err(map_read_err)?;
+    if preserve.xattr { // set before the mode, which may make the file read-only
+        copy_xattrs(in_path, out_path).map_err(map_write_err)?;
+    }
+    if preserve.timestamps {
+        let times = fs::FileTimes::new()
+            .set_accessed(metadata.accessed().map_err(map_read_err)?)
+            .set_modified(metadata.modified().map_err(map_read_err)?);
+        out_file.set_times(times).map_err(map_write_err)?;
+    }
+    if preserve.mode {
+        out_file.set_permissions(metadata.permissions()).map_err(map_write_err)?;
+    }
+    return Ok(());
+}
+
+#[cfg(unix)]
+fn copy_xattrs(in_path: &path::PathBuf, out_path: &path::PathBuf) -> io::Result<()> {
+    for name in xattr::list(in_path)? {
+        if let Some(value) = xattr::get(in_path, &name)? {
+            xattr::set(out_path, &name, &value)?;
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_in_path: &path::PathBuf, _out_path: &path::PathBuf) -> io::Result<()> {
+    return Ok(()); // extended attributes are not supported
+}
+
+/// Identifies a file by the device and inode numbers on Unix, or by the canonical path elsewhere.
+#[cfg(unix)]
+pub type FileId = (u64, u64);
+#[cfg(not(unix))]
+pub type FileId = path::PathBuf;
+
+/// Returns the identity of the file, following symbolic links. None if the file can't be accessed.
+pub fn file_id(file_path: &path::Path) -> Option<FileId> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        return fs::metadata(file_path).ok().map(|m| (m.dev(), m.ino()));
+    }
+    #[cfg(not(unix))]
+    {
+        return fs::canonicalize(file_path).ok();
+    }
+}
+
+/// Whether both paths point to the same file, which is found through hard links as well on Unix.
+pub fn is_same_file(path1: &path::Path, path2: &path::Path) -> bool {
+    let id1 = file_id(path1);
+    return id1.is_some() && id1 == file_id(path2);
+}
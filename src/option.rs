@@ -1,11 +1,16 @@
+use crate::metadata;
+
 use structopt::StructOpt;
+use transcoding_rs as tc;
+use globset;
 use std::path::PathBuf;
 
 /// Converts texts from the auto-detected encoding to UTF-8 or a specified encoding.
 /// If byte sequences that is malformed as Unicode are found,
 /// they are replaced with the REPLACEMENT CHARACTER(U+FFFD).
 /// If the destination encoding is not Unicode and unmappable characters are found, they are
-/// replaced with the corresponding numeric character references.
+/// replaced with the corresponding numeric character references by default, which can be changed with --unmappable.
+/// With the --strict flag, they are treated as errors instead.
 /// If the encoding detection is considered it failed, the input texts are output as-is,
 /// meaning no conversion takes place, and an error message is emitted.
 #[derive(StructOpt, Debug, Default)]
@@ -19,11 +24,58 @@ pub struct Opt {
     #[structopt(name = "ENCODING", short = "t", long = "to-code", default_value = "UTF-8")]
     pub to_code: String,
 
+    /// The encoding of the input.
+    /// If specified, the encoding detection is skipped and the input is decoded with ENCODING.
+    #[structopt(short = "f", long = "from-code", value_name = "ENCODING")]
+    pub from_code: Option<String>,
+
     /// Output directory.
     /// If input arguments contain directories, the directory hierarchies are preserved under DIRECTORY.
     #[structopt(name = "DIRECTORY", short = "o", long = "output", parse(from_os_str))]
     pub output: Option<PathBuf>,
 
+    /// Keeps the files that already exist in the output directory instead of overwriting them.
+    #[structopt(long, conflicts_with_all = &["force", "BACKUP_SUFFIX"])]
+    pub no_clobber: bool,
+
+    /// Removes and recreates the files in the output directory that can't be opened for writing, like read-only files,
+    /// and overwrites the backup files of --in-place that already exist.
+    #[structopt(long)]
+    pub force: bool,
+
+    /// Renames the files that already exist in the output directory by appending SUFFIX (defaults to '~')
+    /// before writing them.
+    #[structopt(name = "BACKUP_SUFFIX", value_name = "SUFFIX", long = "backup", require_equals = true, min_values = 0)]
+    pub backup: Option<Option<String>>,
+
+    /// Preserves the metadata of the input files on the files in the output directory.
+    /// ATTRS are comma-separated: mode (permissions), timestamps (access and modification times)
+    /// and xattr (extended attributes, Unix only). If ATTRS are omitted, mode and timestamps are preserved.
+    /// Files converted in place always keep their mode and timestamps, and xattr applies to them as well.
+    #[structopt(name = "ATTRS", long = "preserve", require_equals = true, min_values = 0, use_delimiter = true, possible_values = &["mode", "timestamps", "xattr"])]
+    pub preserve: Option<Vec<PreserveItem>>,
+
+    /// Skips symbolic links found while traversing directories, which are followed by default.
+    /// Paths given as arguments are always followed.
+    /// Symbolic links that loop back to their ancestor directories are reported as failures without stopping the conversion.
+    #[structopt(long)]
+    pub no_follow_symlinks: bool,
+
+    /// Recreates symbolic links found while traversing directories as symbolic links to the same targets
+    /// in the output directory, instead of converting the files they point to.
+    /// Absolute targets in the input directories are rewritten to point to the same files in the output directory.
+    #[structopt(long)]
+    pub recreate_symlinks: bool,
+
+    /// Converts files in place.
+    /// Each file is transcoded to a temporary file in the same directory, which then replaces the original file
+    /// preserving its permissions and modification time.
+    /// If SUFFIX is given, the original file is kept as a backup with SUFFIX appended to its name,
+    /// which is not overwritten if it already exists unless --force is given.
+    /// For a symbolic link, the file it points to is converted and the link is kept.
+    #[structopt(short = "i", long = "in-place", value_name = "SUFFIX", require_equals = true, min_values = 0)]
+    pub in_place: Option<Option<String>>,
+
     /// Prints supported encodings.
     #[structopt(short, long)]
     pub list: bool,
@@ -40,10 +92,127 @@ pub struct Opt {
     #[structopt(name = "NUMBER", short = "A", long = "non_ascii_to_guess", default_value = "100")]
     pub non_ascii_to_guess: usize,
 
+    /// Comma-separated encodings that the encoding detection can pick from.
+    /// If the guessed encoding is not one of them, the most likely one of them is used instead.
+    #[structopt(name = "ENCODINGS", long = "candidates", use_delimiter = true, parse(try_from_str = parse_encoding))]
+    pub candidates: Vec<tc::Encoding>,
+
+    /// The top-level domain (e.g. jp, ru, cz) which hints the encoding detection at the locale of the input.
+    #[structopt(name = "TLD", long = "tld", parse(try_from_str = parse_tld))]
+    pub tld: Option<String>,
+
+    /// The language (e.g. ja, ru, zh-TW) of the input, which hints the encoding detection like --tld.
+    /// --tld takes precedence if both are specified.
+    #[structopt(name = "LANGUAGE", long = "lang", parse(try_from_str = parse_language))]
+    pub lang: Option<String>,
+
+    /// Disables the heuristics that detect BOM-less UTF-16 and UTF-32 by null bytes.
+    #[structopt(long)]
+    pub no_bomless_heuristics: bool,
+
+    /// Stops with an error at the first malformed byte sequence or unmappable character
+    /// instead of replacing it.
+    #[structopt(long)]
+    pub strict: bool,
+
+    /// How to handle characters that can't be mapped to the destination encoding.
+    /// One of ncr (numeric character references), substitute[=CHAR] (CHAR defaults to '?'), skip,
+    /// escape (\uXXXX escapes), translit (transliteration to the closest ASCII) or error.
+    #[structopt(name = "POLICY", long = "unmappable", default_value = "ncr", parse(try_from_str = parse_unmappable))]
+    pub unmappable: tc::UnmappablePolicy,
+
+    /// Converts line endings to lf, crlf or cr, or keeps them as they are (keep).
+    /// This doesn't apply if the encoding detection failed.
+    #[structopt(name = "EOL", long = "eol", default_value = "keep", possible_values = &["lf", "crlf", "cr", "keep"], parse(try_from_str = parse_eol))]
+    pub eol: tc::Eol,
+
+    /// How to handle a BOM when the output encoding is UTF-8, UTF-16 or UTF-32.
+    /// add writes a BOM, remove doesn't, and keep writes one only if the input has a BOM.
+    /// If not specified, a BOM is added to UTF-16 and UTF-32 output.
+    /// This doesn't apply if the encoding detection failed.
+    #[structopt(name = "BOM", long = "bom", possible_values = &["add", "remove", "keep"], parse(try_from_str = parse_bom))]
+    pub bom: Option<tc::Bom>,
+
     /// Only shows auto-detected encodings without decoded texts.
     #[structopt(short, long)]
     pub show: bool,
 
+    /// The output format of --show.
+    /// text prints "FILE: ENCODING" lines, and json prints a JSON object per line with the path, the detected encoding,
+    /// whether a BOM was found, whether the detection failed, the number of bytes examined (0 with -f, which skips the detection)
+    /// and whether malformed byte sequences or unmappable characters were found.
+    #[structopt(name = "FORMAT", long = "format", default_value = "text", possible_values = &["text", "json"])]
+    pub format: Format,
+
+    /// Shows up to N candidate encodings with their scores (0-1) and the reason why the detection failed, if any,
+    /// with --show.
+    #[structopt(name = "N", long = "top")]
+    pub top: Option<usize>,
+
+    /// Checks if the input files are already in the encoding specified by -t without converting them.
+    /// The files that are not are printed with the reasons, and the exit status is 1 if any.
+    /// ASCII-only files are in any ASCII-compatible encoding, and files whose encoding is unknown are not in any.
+    /// ITEMS are comma-separated additional checks:
+    /// bom reports files starting with a BOM and malformed reports files with malformed byte sequences.
+    #[structopt(name = "ITEMS", long = "check", require_equals = true, min_values = 0, use_delimiter = true, possible_values = &["bom", "malformed"])]
+    pub check: Option<Vec<CheckItem>>,
+
+    /// Only converts files whose names or paths relative to the input directories match GLOB
+    /// while traversing directories. Can be specified multiple times.
+    #[structopt(name = "GLOB", long = "include", number_of_values = 1, parse(try_from_str = parse_glob))]
+    pub include: Vec<globset::Glob>,
+
+    /// Skips files and directories whose names or paths relative to the input directories match GLOB
+    /// while traversing directories. Can be specified multiple times.
+    #[structopt(name = "EXCLUDE_GLOB", value_name = "GLOB", long = "exclude", number_of_values = 1, parse(try_from_str = parse_glob))]
+    pub exclude: Vec<globset::Glob>,
+
+    /// Skips hidden files and directories (whose names start with '.') found while traversing directories.
+    #[structopt(long)]
+    pub skip_hidden: bool,
+
+    /// Skips files and directories ignored by .gitignore or .ignore files found while traversing directories.
+    #[structopt(long)]
+    pub use_ignore_files: bool,
+
+    /// What to do with binary files, which are told by magic numbers of common formats or too many non-text bytes.
+    /// With -f, only magic numbers tell binary files. skip doesn't output them, except for a warning on the standard input,
+    /// copy outputs them as-is and error reports them as errors.
+    /// Binary files are not treated as failures of the encoding detection.
+    #[structopt(name = "ACTION", long = "binary", default_value = "skip", possible_values = &["skip", "copy", "error"])]
+    pub binary: BinaryAction,
+
+    /// Converts the text entries of zip and tar archives, including compressed ones like tar.gz,
+    /// instead of treating the archives as binary files.
+    /// rewrite writes a new archive of the same format, and extract extracts the entries into a directory named after
+    /// the archive with ".d" appended (e.g. data.zip.d) in the output directory. Binary entries and the metadata of the entries are kept unchanged.
+    #[structopt(name = "MODE", long = "archive", possible_values = &["rewrite", "extract"])]
+    pub archive: Option<ArchiveAction>,
+
+    /// What to do with the files compressed with gzip, bzip2, xz or zstd, which are told by magic numbers
+    /// and decompressed to detect the encoding. recompress compresses the output with the same codec,
+    /// decompress writes the output uncompressed, which is named without the extension of the codec
+    /// in the output directory, and binary treats the files as binary files.
+    #[structopt(name = "COMPRESSED_ACTION", value_name = "ACTION", long = "compressed", default_value = "recompress",
+        possible_values = &["recompress", "decompress", "binary"])]
+    pub compressed: CompressedAction,
+
+    /// The number of files converted in parallel while traversing directories. 0 means the number of CPUs.
+    /// The output to the standard output stays in the order the files are found.
+    #[structopt(name = "JOBS", value_name = "N", short = "j", long = "jobs", default_value = "1")]
+    pub jobs: usize,
+
+    /// Continues with the other files on any errors, such as unreadable files, instead of stopping at the first one.
+    /// A summary of the files converted, unchanged, skipped and failed is printed to stderr at the end,
+    /// and the exit status reflects the most severe error.
+    #[structopt(long)]
+    pub keep_going: bool,
+
+    /// Prints what would be converted without creating any directories or files:
+    /// the detected encoding, the output encoding, and the output path with whether it would be created or overwritten.
+    #[structopt(long, conflicts_with_all = &["show", "ITEMS"])]
+    pub dry_run: bool,
+
     /// Suppresses error messages when encoding detection failed.
     #[structopt(short, long)]
     pub quiet: bool,
@@ -53,13 +222,227 @@ pub struct Opt {
     pub paths: Vec<PathBuf>,
 }
 
+/// The output format of --show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        return Format::Text;
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Invalid format: {}", s)),
+        };
+    }
+}
+
+/// What to do with binary files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryAction {
+    Skip,
+    Copy,
+    Error,
+}
+
+impl Default for BinaryAction {
+    fn default() -> Self {
+        return BinaryAction::Skip;
+    }
+}
+
+impl std::str::FromStr for BinaryAction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "skip" => Ok(BinaryAction::Skip),
+            "copy" => Ok(BinaryAction::Copy),
+            "error" => Ok(BinaryAction::Error),
+            _ => Err(format!("Invalid action: {}", s)),
+        };
+    }
+}
+
+/// What to do with the archives with --archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveAction {
+    Rewrite,
+    Extract,
+}
+
+impl std::str::FromStr for ArchiveAction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "rewrite" => Ok(ArchiveAction::Rewrite),
+            "extract" => Ok(ArchiveAction::Extract),
+            _ => Err(format!("Invalid mode: {}", s)),
+        };
+    }
+}
+
+/// What to do with compressed files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedAction {
+    Recompress,
+    Decompress,
+    Binary,
+}
+
+impl Default for CompressedAction {
+    fn default() -> Self {
+        return CompressedAction::Recompress;
+    }
+}
+
+impl std::str::FromStr for CompressedAction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "recompress" => Ok(CompressedAction::Recompress),
+            "decompress" => Ok(CompressedAction::Decompress),
+            "binary" => Ok(CompressedAction::Binary),
+            _ => Err(format!("Invalid action: {}", s)),
+        };
+    }
+}
+
+/// The metadata preserved with --preserve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreserveItem {
+    Mode,
+    Timestamps,
+    Xattr,
+}
+
+impl std::str::FromStr for PreserveItem {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "mode" => Ok(PreserveItem::Mode),
+            "timestamps" => Ok(PreserveItem::Timestamps),
+            "xattr" => Ok(PreserveItem::Xattr),
+            _ => Err(format!("Invalid attribute: {}", s)),
+        };
+    }
+}
+
+/// An additional check of --check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckItem {
+    Bom,
+    Malformed,
+}
+
+impl std::str::FromStr for CheckItem {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "bom" => Ok(CheckItem::Bom),
+            "malformed" => Ok(CheckItem::Malformed),
+            _ => Err(format!("Invalid check: {}", s)),
+        };
+    }
+}
+
 impl Opt {
     pub fn new() -> Self {
         let mut opt = Opt::default();
         opt.non_ascii_to_guess = 100;
         opt.to_code = "UTF-8".into();
+        opt.jobs = 1;
         return opt;
     }
+
+    /// Whether the converted texts are written, which is false with --show, --check or --dry-run.
+    pub fn writes_output(self: &Self) -> bool {
+        return !self.show && self.check.is_none() && !self.dry_run;
+    }
+
+    /// Whether the output paths are needed, which is true if the output is written or planned with --dry-run.
+    pub fn plans_output(self: &Self) -> bool {
+        return self.writes_output() || self.dry_run;
+    }
+
+    /// The metadata preserved on the files in the output directory.
+    pub fn preserve(self: &Self) -> metadata::Preserve {
+        return match &self.preserve {
+            None => metadata::Preserve::default(),
+            Some(items) if items.is_empty() => metadata::Preserve { mode: true, timestamps: true, xattr: false },
+            Some(items) => metadata::Preserve {
+                mode: items.contains(&PreserveItem::Mode),
+                timestamps: items.contains(&PreserveItem::Timestamps),
+                xattr: items.contains(&PreserveItem::Xattr),
+            },
+        };
+    }
+
+    /// Whether the specified additional check of --check is enabled.
+    pub fn checks(self: &Self, item: CheckItem) -> bool {
+        return self.check.as_ref().map_or(false, |items| items.contains(&item));
+    }
+}
+
+fn parse_encoding(label: &str) -> Result<tc::Encoding, String> {
+    return tc::Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("Invalid encoding: {}", label));
+}
+
+fn parse_tld(tld: &str) -> Result<String, String> {
+    return tc::normalize_tld(tld).ok_or_else(|| format!("Invalid top-level domain: {}", tld));
 }
 
+fn parse_language(language: &str) -> Result<String, String> {
+    return match tc::tld_for_language(language) {
+        Some(_) => Ok(language.into()),
+        None => Err(format!("Unknown language: {}", language)),
+    };
+}
+
+fn parse_glob(s: &str) -> Result<globset::Glob, String> {
+    return globset::Glob::new(s).map_err(|err| format!("Invalid glob: {}", err));
+}
 
+fn parse_eol(s: &str) -> Result<tc::Eol, String> {
+    return match s {
+        "lf" => Ok(tc::Eol::Lf),
+        "crlf" => Ok(tc::Eol::Crlf),
+        "cr" => Ok(tc::Eol::Cr),
+        "keep" => Ok(tc::Eol::Keep),
+        _ => Err(format!("Invalid line ending: {}", s)),
+    };
+}
+
+fn parse_bom(s: &str) -> Result<tc::Bom, String> {
+    return match s {
+        "add" => Ok(tc::Bom::Add),
+        "remove" => Ok(tc::Bom::Remove),
+        "keep" => Ok(tc::Bom::Keep),
+        _ => Err(format!("Invalid BOM policy: {}", s)),
+    };
+}
+
+fn parse_unmappable(s: &str) -> Result<tc::UnmappablePolicy, String> {
+    let (name, arg) = match s.split_once('=') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (s, None),
+    };
+    return match (name, arg) {
+        ("ncr", None) => Ok(tc::UnmappablePolicy::Ncr),
+        ("substitute", None) => Ok(tc::UnmappablePolicy::Substitute('?')),
+        ("substitute", Some(arg)) if arg.chars().count() == 1 => Ok(tc::UnmappablePolicy::Substitute(arg.chars().next().unwrap())),
+        ("skip", None) => Ok(tc::UnmappablePolicy::Skip),
+        ("escape", None) => Ok(tc::UnmappablePolicy::Escape),
+        ("translit", None) => Ok(tc::UnmappablePolicy::Transliterate),
+        ("error", None) => Ok(tc::UnmappablePolicy::Error),
+        _ => Err(format!("Invalid policy: {}", s)),
+    };
+}
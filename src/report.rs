@@ -0,0 +1,84 @@
+use crate::option;
+
+use serde::Serialize;
+use transcoding_rs as tc;
+use std::io;
+use std::path;
+
+/// The result of the encoding detection of a file, which is printed by --show.
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+    pub path: String,
+    /// The detected encoding. None if the detection failed.
+    pub encoding: Option<&'static str>,
+    pub bom: bool,
+    pub failed: bool,
+    /// The number of bytes examined to detect the encoding. 0 if the encoding is specified by -f.
+    pub bytes_examined: usize,
+    /// Whether malformed byte sequences or unmappable characters were found while transcoding.
+    /// None if the input was not transcoded.
+    pub replacement_or_unmappable: Option<bool>,
+    /// The percentage of non-text characters in the examined bytes. None if the encoding was not detected.
+    pub non_text_ratio: Option<f64>,
+    /// The reason why the detection failed.
+    pub rejection: Option<String>,
+    /// The format of the binary file, which is reported only if the input is binary.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary: Option<&'static str>,
+    /// The candidate encodings, which are reported only if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidates: Option<Vec<Candidate>>,
+}
+
+/// A candidate encoding with its score.
+#[derive(Serialize, Debug)]
+pub struct Candidate {
+    pub encoding: &'static str,
+    pub score: f64,
+}
+
+impl Report {
+    pub fn new(relative_path: &path::PathBuf) -> Self {
+        return Report { path: relative_path.to_string_lossy().into(), ..Default::default() };
+    }
+
+    /// Fills in the details of the detection, reporting up to `top` candidates if specified.
+    pub fn detection(self: &mut Self, detection: &tc::Detection, top: Option<usize>) {
+        self.non_text_ratio = Some(detection.non_text_ratio);
+        self.rejection = detection.rejection.as_ref().map(|r| r.to_string());
+        self.candidates = top.map(|n| detection.top(n).iter()
+            .map(|c| Candidate { encoding: c.encoding.name(), score: c.score })
+            .collect());
+    }
+
+    /// Writes the report in the specified format.
+    /// In the text format, nothing is written if the detection failed unless the candidates are reported.
+    pub fn write(self: &Self, writer: &mut dyn io::Write, format: option::Format) -> io::Result<()> {
+        match format {
+            option::Format::Text => {
+                if let Some(format) = self.binary {
+                    writer.write_fmt(format_args!("{}: binary ({})\n", self.path, format))?;
+                    return Ok(());
+                }
+                match (self.encoding, &self.candidates) {
+                    (Some(encoding), None) => writer.write_fmt(format_args!("{}: {}\n", self.path, encoding))?,
+                    (encoding, Some(candidates)) => {
+                        let candidates: Vec<String> = candidates.iter()
+                            .map(|c| format!("{} {:.2}", c.encoding, c.score))
+                            .collect();
+                        writer.write_fmt(format_args!("{}: {} ({})\n", self.path, encoding.unwrap_or("(failed)"), candidates.join(", ")))?;
+                        if let Some(rejection) = &self.rejection {
+                            writer.write_fmt(format_args!("{}: {}\n", self.path, rejection))?;
+                        }
+                    },
+                    (None, None) => {},
+                }
+            },
+            option::Format::Json => {
+                serde_json::to_writer(&mut *writer, self)?;
+                writer.write_all(b"\n")?;
+            },
+        }
+        return Ok(());
+    }
+}
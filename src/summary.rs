@@ -0,0 +1,73 @@
+use crate::option;
+use crate::error;
+use crate::transcode;
+
+use std::io;
+use std::path;
+
+/// The results of the files, which are printed at the end with --keep-going.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub converted: usize,
+    pub unchanged: usize,
+    pub skipped: usize,
+    /// The error messages of the files that failed.
+    pub failures: Vec<String>,
+    /// The most severe error, which decides the exit code.
+    worst: Option<error::Error>,
+}
+
+impl Summary {
+    /// Records the result of a file.
+    /// Errors on the contents, or any errors but a broken pipe with --keep-going, are recorded as failures,
+    /// and the other errors are returned as they are to stop the run.
+    pub fn record(self: &mut Self, relative_path: &path::PathBuf, result: Result<transcode::Outcome, error::Error>,
+        opt: &option::Opt) -> Result<(), error::Error> {
+        let err = match result {
+            Ok(transcode::Outcome::Converted) => { self.converted += 1; return Ok(()); },
+            Ok(transcode::Outcome::Unchanged) => { self.unchanged += 1; return Ok(()); },
+            Ok(transcode::Outcome::Skipped) => { self.skipped += 1; return Ok(()); },
+            Err(err) => err,
+        };
+        let continues = err.is_guess() || err.is_check() || err.is_binary() || (opt.keep_going && ! err.is_broken_pipe());
+        if ! continues {
+            return Err(err);
+        }
+        self.fail(relative_path, err);
+        return Ok(());
+    }
+
+    /// Records the error of a file as a failure without stopping the run, reporting it to stderr unless it's reported.
+    pub fn fail(self: &mut Self, relative_path: &path::PathBuf, err: error::Error) {
+        if ! err.is_reported() { // report it now as it would be when stopping
+            eprintln!("{}", err);
+        }
+        if err.is_guess() { // the message of a guess error has no path
+            self.failures.push(format!("{}: {}", relative_path.to_string_lossy(), err));
+        } else {
+            self.failures.push(err.to_string());
+        }
+        if self.worst.as_ref().map_or(true, |worst| worst.severity() < err.severity()) {
+            self.worst = Some(err);
+        }
+    }
+
+    /// Writes the numbers of the files followed by the failures.
+    pub fn write(self: &Self, writer: &mut dyn io::Write) -> io::Result<()> {
+        writer.write_fmt(format_args!("{} converted, {} unchanged, {} skipped, {} failed\n",
+            self.converted, self.unchanged, self.skipped, self.failures.len()))?;
+        for failure in &self.failures {
+            writer.write_fmt(format_args!("  {}\n", failure))?;
+        }
+        return Ok(());
+    }
+
+    /// Returns the most severe error recorded, if any.
+    pub fn into_result(self: Self) -> Result<(), error::Error> {
+        return match self.worst {
+            None => Ok(()),
+            Some(err) if err.is_reported() => Err(err),
+            Some(err) => Err(error::Error::Reported(Box::new(err))),
+        };
+    }
+}
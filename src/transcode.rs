@@ -1,14 +1,75 @@
 use crate::option;
 use crate::error;
+use crate::report;
 
 use transcoding_rs as tc;
 use encoding_rs as enc;
 use std::io;
+use std::io::Read;
 use std::io::Write;
 use std::path;
+use std::cell;
 
-pub fn transcode(reader: &mut dyn io::Read, writer: &mut dyn io::Write, encoding: &'static enc::Encoding, opt: &option::Opt, relative_path: &path::PathBuf)
-    -> Result<(), error::Error> {
+/// The size of the head of the input examined to tell if it's binary.
+pub const SNIFF_SIZE: u64 = 8 * 1024;
+
+/// Reads the head of the input to tell if it's binary.
+/// Returns the reader that yields the whole input including the head, and the format of the binary if it is.
+pub fn sniff<R: io::Read>(mut reader: R, relative_path: &path::PathBuf)
+    -> Result<(io::Chain<io::Cursor<Vec<u8>>, R>, Option<&'static str>), error::Error> {
+    let mut head = Vec::new();
+    (&mut reader).take(SNIFF_SIZE).read_to_end(&mut head)
+        .map_err(|err| error::Error::Io { source: err, path: relative_path.into(), message: "Error reading the file".into() })?;
+    let format = tc::binary_format(&head);
+    return Ok((io::Cursor::new(head).chain(reader), format));
+}
+
+/// What became of a file, which is counted in the summary of --keep-going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Transcoded from another encoding.
+    Converted,
+    /// Already in the destination encoding, empty, or output as-is.
+    Unchanged,
+    /// Not output, like binary files.
+    Skipped,
+}
+
+/// Handles the binary input according to --binary, or reports it with --show.
+pub fn binary(reader: &mut dyn io::Read, writer: &mut dyn io::Write, format: &'static str, opt: &option::Opt, relative_path: &path::PathBuf)
+    -> Result<Outcome, error::Error> {
+    let map_write_err= |err :io::Error| -> error::Error {
+        match err.kind() {
+            io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+            _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the file".into() }
+        }
+    };
+    if opt.show {
+        let mut report = report::Report::new(relative_path);
+        report.binary = Some(format);
+        report.write(writer, opt.format).map_err(map_write_err)?;
+        return Ok(Outcome::Skipped);
+    }
+    match opt.binary {
+        option::BinaryAction::Skip => {},
+        option::BinaryAction::Copy => {
+            if opt.writes_output() {
+                copy(reader, writer, relative_path)?; // write input to output as-is
+                return Ok(Outcome::Unchanged);
+            }
+        },
+        option::BinaryAction::Error => {
+            let msg = format!("{}: Binary file ({}) found.", relative_path.to_string_lossy(), format);
+            let mut stderr = std::io::stderr();
+            stderr.write_fmt(format_args!("{}\n", msg)).map_err(map_write_err)?;
+            return Err(error::Error::Binary(msg));
+        },
+    }
+    return Ok(Outcome::Skipped);
+}
+
+pub fn transcode(reader: &mut dyn io::Read, writer: &mut dyn io::Write, src_encoding: Option<tc::Encoding>, encoding: tc::Encoding, opt: &option::Opt, relative_path: &path::PathBuf)
+    -> Result<Outcome, error::Error> {
 
     let map_read_err = |err :io::Error| -> error::Error {
         error::Error::Io { source: err, path: relative_path.into(), message: "Error reading the file".into() }
@@ -19,35 +80,69 @@ pub fn transcode(reader: &mut dyn io::Read, writer: &mut dyn io::Write, encoding
             _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the file".into() }
         }
     };
-    let detector = tc::I18nReaderEncodingDetector::new()
-        .buffer_size(10 * 1024)
-        .non_ascii_to_guess(opt.non_ascii_to_guess)
-        .non_text_threshold(opt.non_text_threshold)
-        .add_bom_utf16(true);
-    let guess_result = detector.guess(reader, encoding).map_err(map_read_err)?;
+    // malformed byte sequences are found by stopping at them, which also makes the input transcoded when the encoding is the same.
+    let strict = opt.strict || opt.checks(option::CheckItem::Malformed);
+    let ascii_state = AsciiState::default();
+    let mut ascii_only;
+    let reader: &mut dyn io::Read = if opt.check.is_some() {
+        ascii_only = AsciiOnly { reader, state: &ascii_state };
+        &mut ascii_only
+    } else {
+        reader
+    };
+    if let Some(src_encoding) = src_encoding { // no need to guess
+        let transcoder = tc::Transcoder::for_encodings(Some(src_encoding), encoding)
+            .strict(strict)
+            .unmappable(opt.unmappable);
+        let mut i18n_reader = tc::I18nReader::new(reader, transcoder).eol(opt.eol);
+        // a BOM is added to UTF-16 and UTF-32 by default, in the same way as the detector does by add_bom_utf16.
+        let default_bom = Some(tc::Bom::Add).filter(|_| encoding.bom().is_some() && encoding != enc::UTF_8);
+        if let Some(bom) = opt.bom.or(default_bom) {
+            i18n_reader = i18n_reader.bom(bom);
+        }
+        if opt.show {
+            show(&mut i18n_reader, Some(src_encoding), writer, opt, relative_path)?;
+        } else if opt.check.is_some() {
+            check(&mut i18n_reader, src_encoding, encoding, &ascii_state, writer, opt, relative_path)?;
+        } else {
+            copy(&mut i18n_reader, writer, relative_path)?;
+        }
+        return Ok(outcome(src_encoding, encoding));
+    }
+    let guess_result = detector(opt, strict).guess(reader, encoding).map_err(map_read_err)?;
     match guess_result {
         tc::GuessResult::NoInput => {
             if opt.show {
-                writer.write_fmt(format_args!("{}: {}\n", relative_path.to_string_lossy(), enc::UTF_8.name())).map_err(map_write_err)?;
+                let mut report = report::Report::new(relative_path);
+                report.encoding = Some(enc::UTF_8.name());
+                report.replacement_or_unmappable = Some(false);
+                report.write(writer, opt.format).map_err(map_write_err)?;
             } else {
                 writer.write_all(&[]).map_err(map_write_err)?;
             }
-            return Ok(());
+            return Ok(Outcome::Unchanged);
         },
         tc::GuessResult::Success(mut i18n_reader, enc) => {
             if opt.show {
-                writer.write_fmt(format_args!("{}: {}\n", relative_path.to_string_lossy(), enc.name())).map_err(map_write_err)?;
+                show(&mut i18n_reader, Some(enc), writer, opt, relative_path)?;
+            } else if opt.check.is_some() {
+                check(&mut i18n_reader, enc, encoding, &ascii_state, writer, opt, relative_path)?;
             } else {
-                io::copy(&mut i18n_reader, writer).map(|_| ()).map_err(map_write_err)?;
+                copy(&mut i18n_reader, writer, relative_path)?;
             }
-            return Ok(());
+            return Ok(outcome(enc, encoding));
         },
         tc::GuessResult::Fail(mut i18n_reader) => { // if no encoding is found
-            if ! opt.show {
-                io::copy(&mut i18n_reader, writer).map(|_| ()).map_err(map_write_err)?; // write input to output as-is
+            if opt.check.is_some() { // the input isn't known to be in the target encoding either
+                return Err(check_failed(vec![format!("unknown encoding (expected {})", encoding.name())], writer, relative_path));
+            }
+            if opt.show {
+                show(&mut i18n_reader, None, writer, opt, relative_path)?;
+            } else {
+                copy(&mut i18n_reader, writer, relative_path)?; // write input to output as-is
             }
             if opt.quiet {
-                return Ok(());
+                return Ok(Outcome::Unchanged);
             }
             let msg = "Encoding detection seemed to fail.";
             let mut stderr = std::io::stderr();
@@ -57,6 +152,201 @@ pub fn transcode(reader: &mut dyn io::Read, writer: &mut dyn io::Write, encoding
     }
 }
 
+/// Reports what converting the input would do without writing the output:
+/// the detected encoding, the destination encoding, and the output path with whether it's created or overwritten.
+/// `destination` describes the output file, which is None if the output would be written to stdout.
+pub fn plan(reader: &mut dyn io::Read, writer: &mut dyn io::Write, binary_format: Option<&'static str>,
+    src_encoding: Option<tc::Encoding>, encoding: tc::Encoding, destination: Option<String>, opt: &option::Opt,
+    relative_path: &path::PathBuf) -> Result<Outcome, error::Error> {
+    let destination = destination.unwrap_or_else(|| "-".into());
+    let (line, outcome) = match (binary_format, src_encoding) {
+        (Some(format), _) if opt.binary != option::BinaryAction::Copy || opt.in_place.is_some() => {
+            let action = if opt.binary == option::BinaryAction::Error { "error" } else { "skipped" };
+            (format!("binary ({}), {}", format, action), Outcome::Skipped)
+        },
+        (Some(format), _) => (format!("binary ({}) -> as-is, {}", format, destination), Outcome::Unchanged),
+        (None, Some(src_encoding)) => {
+            (format!("{} -> {}, {}", src_encoding.name(), encoding.name(), destination), outcome(src_encoding, encoding))
+        },
+        (None, None) => {
+            let guess_result = detector(opt, opt.strict).guess(reader, encoding)
+                .map_err(|err| error::Error::Io { source: err, path: relative_path.into(), message: "Error reading the file".into() })?;
+            match guess_result {
+                tc::GuessResult::NoInput => (format!("(empty) -> {}, {}", encoding.name(), destination), Outcome::Unchanged),
+                tc::GuessResult::Success(_, enc) => {
+                    (format!("{} -> {}, {}", enc.name(), encoding.name(), destination), outcome(enc, encoding))
+                },
+                tc::GuessResult::Fail(_) => (format!("(failed) -> as-is, {}", destination), Outcome::Unchanged),
+            }
+        },
+    };
+    writer.write_fmt(format_args!("{}: {}\n", relative_path.to_string_lossy(), line)).map_err(|err| match err.kind() {
+        io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+        _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the file".into() }
+    })?;
+    return Ok(outcome);
+}
+
+fn detector(opt: &option::Opt, strict: bool) -> tc::I18nReaderEncodingDetector {
+    let mut detector = tc::I18nReaderEncodingDetector::new()
+        .buffer_size(10 * 1024)
+        .non_ascii_to_guess(opt.non_ascii_to_guess)
+        .non_text_threshold(opt.non_text_threshold)
+        .add_bom_utf16(true)
+        .strict(strict)
+        .unmappable(opt.unmappable)
+        .allowed_encodings(&opt.candidates)
+        .bomless_heuristics(!opt.no_bomless_heuristics)
+        .rank_candidates(opt.top.is_some())
+        .eol(opt.eol);
+    if let Some(bom) = opt.bom {
+        detector = detector.bom(bom);
+    }
+    if let Some(tld) = opt.lang.as_deref().and_then(tc::tld_for_language) { // the language is checked by the option parser
+        detector = detector.tld_hint(tld);
+    }
+    if let Some(tld) = &opt.tld {
+        detector = detector.tld_hint(tld);
+    }
+    return detector;
+}
+
+fn outcome(src_encoding: tc::Encoding, encoding: tc::Encoding) -> Outcome {
+    return if src_encoding == encoding { Outcome::Unchanged } else { Outcome::Converted };
+}
+
+/// Writes the detection report of the file.
+/// In the json format, the input is transcoded to the end to see if malformed byte sequences or unmappable characters
+/// are found.
+fn show<R: io::Read>(i18n_reader: &mut tc::I18nReader<R>, src_encoding: Option<tc::Encoding>, writer: &mut dyn io::Write,
+    opt: &option::Opt, relative_path: &path::PathBuf) -> Result<(), error::Error> {
+    let mut report = report::Report::new(relative_path);
+    report.encoding = src_encoding.map(|e| e.name());
+    report.failed = src_encoding.is_none();
+    if opt.format == option::Format::Json && src_encoding.is_some() {
+        let stopped = match copy(i18n_reader, &mut io::sink(), relative_path) {
+            Ok(_) => false,
+            Err(error::Error::Transcode{..}) => true, // stopped in the strict mode
+            Err(err) => return Err(err),
+        };
+        report.replacement_or_unmappable = Some(stopped || i18n_reader.had_replacement_or_unmappable());
+    }
+    if let Some(detection) = i18n_reader.detection() {
+        report.detection(detection, opt.top);
+    }
+    report.bom = i18n_reader.had_bom();
+    report.bytes_examined = i18n_reader.bytes_guessed();
+    report.write(writer, opt.format).map_err(|err| match err.kind() {
+        io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+        _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the file".into() }
+    })?;
+    return Ok(());
+}
+
+/// Checks if the input is already in the target encoding, writing the reasons to the writer if not.
+/// If BOMs or malformed byte sequences are checked, the input is transcoded to the end without output.
+fn check<R: io::Read>(i18n_reader: &mut tc::I18nReader<R>, src_encoding: tc::Encoding, encoding: tc::Encoding, ascii_state: &AsciiState,
+    writer: &mut dyn io::Write, opt: &option::Opt, relative_path: &path::PathBuf) -> Result<(), error::Error> {
+    let mut reasons: Vec<String> = Vec::new();
+    let mut malformed = None;
+    // the input is read to the end to see if it's all ASCII as well when the encoding differs.
+    if src_encoding != encoding || opt.checks(option::CheckItem::Bom) || opt.checks(option::CheckItem::Malformed) {
+        match copy(i18n_reader, &mut io::sink(), relative_path) {
+            Ok(_) => {},
+            Err(error::Error::Transcode{source: source @ tc::TranscodeError::Malformed{..}, ..}) => {
+                if opt.checks(option::CheckItem::Malformed) {
+                    malformed = Some(source);
+                }
+            },
+            Err(error::Error::Transcode{..}) => {}, // unmappable characters are found only if the encoding differs
+            Err(err) => return Err(err),
+        }
+    }
+    // ASCII is valid in the target encoding as well if it's ASCII-compatible, whichever encoding was detected.
+    let ascii_compatible = encoding.as_standard().map_or(false, |e| e.is_ascii_compatible());
+    if src_encoding != encoding && !(ascii_compatible && ascii_state.is_ascii()) {
+        reasons.push(format!("{} (expected {})", src_encoding.name(), encoding.name()));
+    }
+    if let Some(source) = malformed {
+        reasons.push(source.to_string());
+    }
+    if opt.checks(option::CheckItem::Bom) && i18n_reader.had_bom() {
+        reasons.push("BOM found".into());
+    }
+    if reasons.is_empty() {
+        return Ok(());
+    }
+    return Err(check_failed(reasons, writer, relative_path));
+}
+
+/// Lists the file with the reasons it failed --check, and returns the error for it.
+fn check_failed(reasons: Vec<String>, writer: &mut dyn io::Write, relative_path: &path::PathBuf) -> error::Error {
+    let message = format!("{}: {}", relative_path.to_string_lossy(), reasons.join(", "));
+    if let Err(err) = writer.write_fmt(format_args!("{}\n", message)) {
+        return match err.kind() {
+            io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+            _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the file".into() }
+        };
+    }
+    return error::Error::Check(message);
+}
+
+/// Whether the input read through `AsciiOnly` is all ASCII.
+#[derive(Debug, Default)]
+struct AsciiState {
+    non_ascii: cell::Cell<bool>,
+    eof: cell::Cell<bool>,
+}
+
+impl AsciiState {
+    /// True only if the input has been read to the end.
+    fn is_ascii(self: &Self) -> bool {
+        return self.eof.get() && !self.non_ascii.get();
+    }
+}
+
+/// Reader that keeps track of whether the input is all ASCII.
+/// ESC is not counted as ASCII, since it starts the escape sequences of ISO-2022-JP.
+struct AsciiOnly<'a> {
+    reader: &'a mut dyn io::Read,
+    state: &'a AsciiState,
+}
+
+impl io::Read for AsciiOnly<'_> {
+    fn read(self: &mut Self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.state.eof.set(true);
+        }
+        if buf[..n].iter().any(|b| *b >= 0x80 || *b == 0x1B) {
+            self.state.non_ascii.set(true);
+        }
+        return Ok(n);
+    }
+}
+
+/// Copies the reader to the writer like `io::copy`, telling read errors from write errors.
+fn copy(reader: &mut dyn io::Read, writer: &mut dyn io::Write, relative_path: &path::PathBuf) -> Result<(), error::Error> {
+    let buf = &mut [0u8; 8*1024];
+    loop {
+        let n = match reader.read(buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                if let Some(source) = err.get_ref().and_then(|e| e.downcast_ref::<tc::TranscodeError>()) {
+                    return Err(error::Error::Transcode { source: source.clone(), path: relative_path.into() });
+                }
+                return Err(error::Error::Io { source: err, path: relative_path.into(), message: "Error reading the file".into() });
+            },
+        };
+        writer.write_all(&buf[..n]).map_err(|err| match err.kind() {
+            io::ErrorKind::BrokenPipe => error::Error::BrokenPipe, // Ignore broken pipe error. rust-lang/rust#46016
+            _ => error::Error::Io { source: err, path: relative_path.into(), message: "Error writing the file".into() }
+        })?;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path;
@@ -69,9 +359,9 @@ mod tests {
                 let opt = super::option::Opt::new();
                 let test_data = path::Path::new("test_data");
                 let ifile_handle = &mut std::fs::File::open(test_data.join($input_file)).unwrap();
-                let enc = super::enc::Encoding::for_label($enc.as_bytes()).unwrap_or(&super::enc::UTF_8_INIT);
+                let enc = super::tc::Encoding::for_label($enc.as_bytes()).unwrap_or(super::enc::UTF_8.into());
                 let output = &mut Vec::with_capacity(20*1024);
-                let _ = super::transcode(ifile_handle, output, enc, &opt, &"_".into());
+                let _ = super::transcode(ifile_handle, output, None, enc, &opt, &"_".into());
                 let efile_handle = &mut std::fs::File::open(test_data.join($expected_file)).unwrap();
                 let expected_string = &mut Vec::with_capacity(20*1024);
                 efile_handle.read_to_end(expected_string).unwrap();
@@ -86,6 +376,8 @@ mod tests {
     }
     test_transcode!(transcode_utf16le_utf8     , "utf16le_BOM_th.txt"  , "utf8_th.txt"     , "utf8");
     test_transcode!(transcode_utf16be_utf8     , "utf16be_BOM_th.txt"  , "utf8_th.txt"     , "utf8");
+    test_transcode!(transcode_utf32le_utf8     , "utf32le_BOM_th.txt"  , "utf8_th.txt"     , "utf8");
+    test_transcode!(transcode_utf32be_utf8     , "utf32be_BOM_th.txt"  , "utf8_th.txt"     , "utf8");
     test_transcode!(transcode_sjis_utf8        , "sjis_ja.txt"         , "utf8_ja.txt"     , "utf8");
     test_transcode!(transcode_eucjp_utf8       , "euc-jp_ja.txt"       , "utf8_ja.txt"     , "utf8");
     test_transcode!(transcode_iso2022jp_utf8   , "iso-2022-jp_ja.txt"  , "utf8_ja.txt"     , "utf8");
@@ -99,6 +391,8 @@ mod tests {
 
     test_transcode!(transcode_utf8_utf16le     , "utf8_th.txt"     , "utf16le_BOM_th.txt"  , "utf-16le"     );
     test_transcode!(transcode_utf8_utf16be     , "utf8_th.txt"     , "utf16be_BOM_th.txt"  , "utf-16be"     );
+    test_transcode!(transcode_utf8_utf32le     , "utf8_th.txt"     , "utf32le_BOM_th.txt"  , "utf-32le"     );
+    test_transcode!(transcode_utf8_utf32be     , "utf8_th.txt"     , "utf32be_BOM_th.txt"  , "utf-32be"     );
     test_transcode!(transcode_utf8_sjis        , "utf8_ja.txt"     , "sjis_ja.txt"         , "sjis"         );
     test_transcode!(transcode_utf8_eucjp       , "utf8_ja.txt"     , "euc-jp_ja.txt"       , "euc-jp"       );
     test_transcode!(transcode_utf8_iso2022jp   , "utf8_ja.txt"     , "iso-2022-jp_ja.txt"  , "iso-2022-jp"  );
@@ -112,5 +406,14 @@ mod tests {
 
     test_transcode!(transcode_no_encoding_binary , "binary.jpeg"     , "binary.jpeg"         , "binary"     );
     test_transcode!(transcode_no_encoding_utf8   , "utf8_th.txt"     , "utf8_th.txt"         , "utf8"       );
+
+    #[test]
+    fn transcode_from_code() {
+        let opt = super::option::Opt::new();
+        let input = b"\x83\x6E\x83\x8D\x81\x5B"; // ハロー in SHIFT_JIS
+        let output = &mut Vec::new();
+        super::transcode(&mut input.as_ref(), output, Some(super::enc::SHIFT_JIS.into()), super::enc::EUC_JP.into(), &opt, &"_".into()).unwrap();
+        assert_eq!(b"\xA5\xCF\xA5\xED\xA1\xBC", &output[..]); // ハロー in EUC_JP
+    }
 }
 
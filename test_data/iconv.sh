@@ -2,6 +2,9 @@ echo -en "\xFF\xFE" > utf16le_BOM_th.txt && iconv -t utf-16le      utf8_th.txt
 echo -en "\xFE\xFF" > utf16be_BOM_th.txt && iconv -t utf-16be      utf8_th.txt     >> utf16be_BOM_th.txt
 iconv -t utf-16le      utf8_th.txt     > utf16le_th.txt
 iconv -t utf-16be      utf8_th.txt     > utf16be_th.txt
+echo -en "\xFF\xFE\x00\x00" > utf32le_BOM_th.txt && iconv -t utf-32le      utf8_th.txt     >> utf32le_BOM_th.txt
+echo -en "\x00\x00\xFE\xFF" > utf32be_BOM_th.txt && iconv -t utf-32be      utf8_th.txt     >> utf32be_BOM_th.txt
+iconv -t utf-32le      utf8_th.txt     > utf32le_th.txt
 iconv -t sjis          utf8_ja.txt     > sjis_ja.txt
 iconv -t euc-jp        utf8_ja.txt     > euc-jp_ja.txt
 iconv -t iso-2022-jp   utf8_ja.txt     > iso-2022-jp_ja.txt
@@ -48,6 +48,92 @@ fn to_code() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn from_code() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg("test_data/sjis_ja.txt")
+        .args(&["-f","sjis"])
+        .assert().success();
+    assert_eq!(read_file("test_data/utf8_ja.txt"), assert.get_output().stdout);
+    Ok(())
+}
+
+#[test]
+fn error_from_code() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-f","no-such-encoding"])
+        .write_stdin("abc")
+        .assert()
+        .code(exitcode::USAGE)
+        .stderr("Invalid encoding: no-such-encoding\n");
+    Ok(())
+}
+
+#[test]
+fn in_place() -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = std::path::PathBuf::from("output/in_place");
+    let _ = std::fs::remove_dir_all(&out_dir);
+    std::fs::create_dir_all(&out_dir)?;
+    let target = out_dir.join("sjis_ja.txt");
+    std::fs::copy("test_data/sjis_ja.txt", &target)?;
+    let mtime = std::fs::metadata(&target)?.modified()?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&target).arg("--in-place=.bak").assert().success();
+
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(&target));
+    assert_eq!(read_file("test_data/sjis_ja.txt"), read_file(out_dir.join("sjis_ja.txt.bak")));
+    assert_eq!(mtime, std::fs::metadata(&target)?.modified()?);
+
+    // the existing backup is kept without --force
+    std::fs::copy("test_data/euc-jp_ja.txt", &target)?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&target).arg("--in-place=.bak").assert().code(exitcode::IOERR);
+    assert_eq!(read_file("test_data/euc-jp_ja.txt"), read_file(&target));
+    assert_eq!(read_file("test_data/sjis_ja.txt"), read_file(out_dir.join("sjis_ja.txt.bak")));
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&target).args(&["--in-place=.bak","--force"]).assert().success();
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(&target));
+    assert_eq!(read_file("test_data/euc-jp_ja.txt"), read_file(out_dir.join("sjis_ja.txt.bak")));
+
+    // the target of a symbolic link is converted and the link is kept
+    let link = out_dir.join("link.txt");
+    std::fs::copy("test_data/sjis_ja.txt", &target)?;
+    std::os::unix::fs::symlink("sjis_ja.txt", &link)?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&link).arg("--in-place").assert().success();
+    assert!(std::fs::symlink_metadata(&link)?.file_type().is_symlink());
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(&target));
+    Ok(())
+}
+
+#[test]
+fn strict() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-t","sjis","--strict"])
+        .write_stdin("abc\u{00E9}def")
+        .assert()
+        .code(aconv::error::ILSEQ)
+        .stdout("abc")
+        .stderr("-: Unmappable character '\u{00E9}' (U+00E9) found at byte offset 3.\n");
+    Ok(())
+}
+
+#[test]
+fn unmappable() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-t","sjis","--unmappable","substitute"])
+        .write_stdin("caf\u{00E9}")
+        .assert().success()
+        .stdout("caf?");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-t","sjis","--unmappable","error"])
+        .write_stdin("caf\u{00E9}")
+        .assert()
+        .code(aconv::error::ILSEQ)
+        .stdout("caf");
+    Ok(())
+}
+
 #[test]
 fn threshold() -> Result<(), Box<dyn std::error::Error>> {
     let mut cmd = Command::cargo_bin("aconv")?;
@@ -74,6 +160,582 @@ fn show() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn show_json() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data/to_code").args(&["-s","--format","json"])
+        .assert().success()
+        .stdout(concat!(r#"{"path":"test_data/to_code/eucjp_to_sjis.txt","encoding":"EUC-JP","bom":false,"failed":false,"#,
+            r#""bytes_examined":15,"replacement_or_unmappable":false,"non_text_ratio":0.0,"rejection":null}"#, "\n"));
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-s","-q","--format","json"])
+        .write_stdin("a\x00b\x00c\x00d\x00e\x00")
+        .assert().success()
+        .stdout(concat!(r#"{"path":"-","encoding":null,"bom":false,"failed":true,"#,
+            r#""bytes_examined":10,"replacement_or_unmappable":null,"non_text_ratio":50.0,"#,
+            r#""rejection":"Non-text characters were 50.0%, which is above the threshold 0%"}"#, "\n"));
+    Ok(())
+}
+
+#[test]
+fn show_top() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data/sjis_ja.txt").args(&["-s","--top","2"])
+        .assert().success()
+        .stdout("test_data/sjis_ja.txt: Shift_JIS (Shift_JIS 0.91, GBK 0.06)\n");
+    Ok(())
+}
+
+#[test]
+fn candidates() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["--candidates","sjis,euc-jp,utf-8"])
+        .write_stdin(&b"\x93\x8C\x8B\x9E"[..]) // 東京 in Shift_JIS
+        .assert().success()
+        .stdout("東京");
+    Ok(())
+}
+
+#[test]
+fn tld() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-s","--tld","jp"])
+        .write_stdin(&b"\x93\x8C\x8B\x9E"[..]) // 東京 in Shift_JIS
+        .assert().success()
+        .stdout("-: Shift_JIS\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-s","--lang","ja"])
+        .write_stdin(&b"\x93\x8C\x8B\x9E"[..])
+        .assert().success()
+        .stdout("-: Shift_JIS\n");
+    Ok(())
+}
+
+#[test]
+fn bomless_utf16() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg("test_data/utf16le_th.txt").assert().success();
+    assert_eq!(read_file("test_data/utf8_th.txt"), assert.get_output().stdout);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data/utf16le_th.txt").args(&["-s","-q","--no-bomless-heuristics"])
+        .assert().success()
+        .stdout("");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.write_stdin("hello world, this is plain text\x00 and more!") // a stray null byte is not UTF-16
+        .assert().code(exitcode::DATAERR);
+    Ok(())
+}
+
+#[test]
+fn utf32() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg("test_data/utf8_th.txt").args(&["-t","utf-32be"]).assert().success();
+    assert_eq!(read_file("test_data/utf32be_BOM_th.txt"), assert.get_output().stdout);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg("test_data/utf32le_BOM_th.txt").args(&["-f","utf-32"]).assert().success();
+    assert_eq!(read_file("test_data/utf8_th.txt"), assert.get_output().stdout);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data/utf32le_th.txt").arg("-s")
+        .assert().success()
+        .stdout("test_data/utf32le_th.txt: UTF-32LE\n");
+    Ok(())
+}
+
+#[test]
+fn eol() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-f","sjis","--eol","lf"]).write_stdin(b"\x83\x6E\r\n\x83\x8D\r\n".as_ref())
+        .assert().success()
+        .stdout("ハ\nロ\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.args(&["-t","utf-16le","--eol","crlf"]).write_stdin("ハ\nロ\r")
+        .assert().success();
+    let expected: Vec<u8> = "\u{FEFF}ハ\r\nロ\r\n".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    assert_eq!(expected, assert.get_output().stdout);
+    Ok(())
+}
+
+#[test]
+fn bom() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["--bom","add"]).write_stdin("abc")
+        .assert().success()
+        .stdout("\u{FEFF}abc");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["--bom","remove"]).write_stdin("\u{FEFF}abc")
+        .assert().success()
+        .stdout("abc");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg("test_data/utf8_th.txt").args(&["-t","utf-16le","--bom","remove"]).assert().success();
+    assert_eq!(read_file("test_data/utf16le_th.txt"), assert.get_output().stdout);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data/utf16le_BOM_th.txt").args(&["-f","utf-16le","--bom","keep"])
+        .assert().success()
+        .stdout([b"\xEF\xBB\xBF".as_ref(), &read_file("test_data/utf8_th.txt")].concat());
+    let mut cmd = Command::cargo_bin("aconv")?; // a BOM is added by default with -f as well as with the detection
+    let assert = cmd.arg("test_data/utf8_th.txt").args(&["-f","utf-8","-t","utf-16le"]).assert().success();
+    assert_eq!(read_file("test_data/utf16le_BOM_th.txt"), assert.get_output().stdout);
+    Ok(())
+}
+
+#[test]
+fn check() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["test_data/sjis_ja.txt","test_data/utf8_ja.txt","--check"])
+        .assert().code(1)
+        .stdout("test_data/sjis_ja.txt: Shift_JIS (expected UTF-8)\n")
+        .stderr("");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["test_data/utf16le_BOM_th.txt","-t","utf-16le","--check"])
+        .assert().success()
+        .stdout("");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["test_data/utf16le_BOM_th.txt","-t","utf-16le","--check=bom"])
+        .assert().code(1)
+        .stdout("test_data/utf16le_BOM_th.txt: BOM found\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["-f","utf-8","--check=malformed"]).write_stdin(b"abc\xFF".as_ref())
+        .assert().code(1)
+        .stdout("-: Malformed byte sequence found at byte offset 3\n");
+    let mut cmd = Command::cargo_bin("aconv")?; // ASCII is valid Shift_JIS
+    cmd.args(&["test_data/ascii_en.txt","-t","sjis","--check"])
+        .assert().success()
+        .stdout("");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["test_data/ascii_en.txt","-t","utf-16le","--check"])
+        .assert().code(1);
+    Ok(())
+}
+
+#[test]
+fn guess_error_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let paths = ["test_data/threshold/threshold.txt", "test_data/sjis_ja.txt"];
+    let mut cmd = Command::cargo_bin("aconv")?; // stops at the input path that failed like a single file
+    let assert = cmd.args(&paths).assert().code(exitcode::DATAERR);
+    assert_eq!(read_file("test_data/threshold/threshold.txt"), assert.get_output().stdout);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.args(&paths).arg("--keep-going").assert().code(exitcode::DATAERR);
+    assert_eq!([read_file("test_data/threshold/threshold.txt"), read_file("test_data/utf8_ja.txt")].concat(), assert.get_output().stdout);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&paths).arg("--check")
+        .assert().code(1)
+        .stdout("test_data/threshold/threshold.txt: unknown encoding (expected UTF-8)\ntest_data/sjis_ja.txt: Shift_JIS (expected UTF-8)\n")
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn filter() -> Result<(), Box<dyn std::error::Error>> {
+    let root = std::path::PathBuf::from("output/filter");
+    std::fs::create_dir_all(root.join("node_modules"))?;
+    std::fs::create_dir_all(root.join("sub"))?;
+    for name in &["a.txt", "b.md", ".hidden.txt", "node_modules/c.txt", "sub/ignored.txt", "sub/kept.txt"] {
+        std::fs::write(root.join(name), "abc")?;
+    }
+    std::fs::write(root.join(".gitignore"), "ignored.txt\n")?;
+    let sorted_stdout = |args: &[&str]| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let output = Command::cargo_bin("aconv")?.arg(&root).args(args).arg("-s").unwrap();
+        let mut lines: Vec<String> = std::str::from_utf8(&output.stdout)?.lines().map(|l| l.to_string()).collect();
+        lines.sort();
+        Ok(lines)
+    };
+    assert_eq!(vec!["output/filter/.gitignore: UTF-8", "output/filter/.hidden.txt: UTF-8", "output/filter/a.txt: UTF-8",
+        "output/filter/b.md: UTF-8", "output/filter/node_modules/c.txt: UTF-8", "output/filter/sub/ignored.txt: UTF-8",
+        "output/filter/sub/kept.txt: UTF-8"], sorted_stdout(&[])?); // hidden files are converted by default
+    assert_eq!(vec!["output/filter/a.txt: UTF-8", "output/filter/sub/kept.txt: UTF-8"],
+        sorted_stdout(&["--include","*.txt","--exclude","node_modules","--use-ignore-files","--skip-hidden"])?);
+    assert_eq!(vec!["output/filter/a.txt: UTF-8", "output/filter/b.md: UTF-8"],
+        sorted_stdout(&["--skip-hidden","--exclude","sub","--exclude","node_modules"])?);
+    Ok(())
+}
+
+#[test]
+fn binary() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data/binary.jpeg")
+        .assert().success()
+        .stdout("")
+        .stderr("");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg("test_data/binary.jpeg").args(&["--binary","copy"]).assert().success();
+    assert_eq!(read_file("test_data/binary.jpeg"), assert.get_output().stdout);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data/binary.jpeg").args(&["--binary","error"])
+        .assert().code(exitcode::DATAERR)
+        .stderr("test_data/binary.jpeg: Binary file (JPEG) found.\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data/binary.jpeg").arg("-s")
+        .assert().success()
+        .stdout("test_data/binary.jpeg: binary (JPEG)\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.pipe_stdin("test_data/binary.jpeg")?
+        .assert().success()
+        .stdout("")
+        .stderr("-: Skipped the binary input (JPEG).\n");
+    Ok(())
+}
+
+#[test]
+fn binary_text() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.write_stdin("ID3 tags are metadata.\n")
+        .assert().success()
+        .stdout("ID3 tags are metadata.\n")
+        .stderr("");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.write_stdin("BZh is the bzip2 magic number.\n")
+        .assert().success()
+        .stdout("BZh is the bzip2 magic number.\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.write_stdin("\x01\x02\x03\x04 control codes\n").args(&["-f","utf-8"])
+        .assert().success()
+        .stdout("\x01\x02\x03\x04 control codes\n");
+    Ok(())
+}
+
+#[test]
+fn jobs() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let sequential = cmd.arg("test_data").arg("-s").assert().failure().get_output().clone();
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("test_data").args(&["-s","-j","4"])
+        .assert().code(sequential.status.code().unwrap())
+        .stdout(sequential.stdout);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.args(&["test_data/sjis_ja.txt","test_data/euc-jp_ja.txt","-j","0"]).assert().success();
+    assert_eq!([read_file("test_data/utf8_ja.txt"), read_file("test_data/utf8_ja.txt")].concat(), assert.get_output().stdout);
+    Ok(())
+}
+
+#[test]
+fn jobs_traversal_error() -> Result<(), Box<dyn std::error::Error>> {
+    let root = std::path::PathBuf::from("output/jobs_traversal_error");
+    let out_dir = root.join("out");
+    let _ = std::fs::remove_dir_all(&root);
+    for dir in ["in/first", "in/second"] {
+        std::fs::create_dir_all(root.join(dir))?;
+        std::fs::copy("test_data/sjis_ja.txt", root.join(dir).join("sjis_ja.txt"))?;
+    }
+    std::fs::create_dir_all(&out_dir)?;
+    std::fs::write(out_dir.join("second"), "")?; // the output directory can't be created
+    for jobs in ["1", "4"] {
+        let _ = std::fs::remove_dir_all(out_dir.join("first"));
+        let mut cmd = Command::cargo_bin("aconv")?;
+        let assert = cmd.arg(root.join("in/first")).arg(root.join("in/second")).arg("-o").arg(&out_dir).args(&["-j", jobs])
+            .assert().code(exitcode::IOERR);
+        let stderr = std::str::from_utf8(&assert.get_output().stderr)?;
+        assert!(stderr.starts_with("output/jobs_traversal_error/out/second: Error creating the directory."), "{}", stderr);
+        assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(out_dir.join("first/sjis_ja.txt")), "-j {}", jobs);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn keep_going() -> Result<(), Box<dyn std::error::Error>> {
+    let root = std::path::PathBuf::from("output/keep_going");
+    std::fs::create_dir_all(&root)?;
+    std::fs::copy("test_data/sjis_ja.txt", root.join("sjis_ja.txt"))?;
+    let dangling = root.join("dangling.txt");
+    if std::fs::symlink_metadata(&dangling).is_err() {
+        std::os::unix::fs::symlink("no_such_file", &dangling)?;
+    }
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&root).arg("-s")
+        .assert().code(exitcode::IOERR)
+        .stderr("output/keep_going/dangling.txt: Error opening the file. Cause: No such file or directory (os error 2)\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg(&root).arg("test_data/binary.jpeg").args(&["--keep-going","-j","2"])
+        .assert().code(exitcode::IOERR);
+    assert_eq!(read_file("test_data/utf8_ja.txt"), assert.get_output().stdout);
+    let stderr = std::str::from_utf8(&assert.get_output().stderr)?;
+    assert!(stderr.ends_with(concat!("1 converted, 0 unchanged, 1 skipped, 1 failed\n",
+        "  output/keep_going/dangling.txt: Error opening the file. Cause: No such file or directory (os error 2)\n")), "{}", stderr);
+    Ok(())
+}
+
+#[test]
+fn dry_run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["test_data/to_code","test_data/binary.jpeg","-o","output/dry_run","--dry-run"])
+        .assert().success()
+        .stdout(concat!("test_data/to_code/eucjp_to_sjis.txt: EUC-JP -> UTF-8, output/dry_run/to_code/eucjp_to_sjis.txt (create)\n",
+            "test_data/binary.jpeg: binary (JPEG), skipped\n"));
+    assert!(! std::path::Path::new("output/dry_run").exists());
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.args(&["test_data/sjis_ja.txt","-t","sjis","-i","--dry-run"])
+        .assert().success()
+        .stdout("test_data/sjis_ja.txt: Shift_JIS -> Shift_JIS, test_data/sjis_ja.txt (overwrite)\n");
+    Ok(())
+}
+
+#[test]
+fn clobber() -> Result<(), Box<dyn std::error::Error>> {
+    let in_dir = std::path::PathBuf::from("output/clobber");
+    let out_dir = in_dir.join("out");
+    let _ = std::fs::remove_dir_all(&in_dir);
+    std::fs::create_dir_all(out_dir.join("clobber"))?;
+    std::fs::copy("test_data/sjis_ja.txt", in_dir.join("sjis_ja.txt"))?;
+    let existing = out_dir.join("clobber/sjis_ja.txt");
+    std::fs::write(&existing, "existing")?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_dir).arg("-o").arg(&out_dir).arg("--no-clobber").assert().success();
+    assert_eq!(b"existing".to_vec(), read_file(&existing));
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_dir).arg("-o").arg(&out_dir).arg("--backup=.bak").assert().success();
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(&existing));
+    assert_eq!(b"existing".to_vec(), read_file(out_dir.join("clobber/sjis_ja.txt.bak")));
+    assert!(! out_dir.join("clobber/out").exists()); // the output directory is not traversed
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(in_dir.join("sjis_ja.txt")).arg("-o").arg(&in_dir)
+        .assert().code(exitcode::IOERR)
+        .stderr("output/clobber/sjis_ja.txt: Error creating the file. Cause: The output file is the input file itself\n");
+    assert_eq!(read_file("test_data/sjis_ja.txt"), read_file(in_dir.join("sjis_ja.txt")));
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn preserve() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let in_dir = std::path::PathBuf::from("output/preserve");
+    let out_dir = in_dir.join("out");
+    let _ = std::fs::remove_dir_all(&in_dir);
+    std::fs::create_dir_all(&out_dir)?;
+    let in_path = in_dir.join("sjis_ja.txt");
+    std::fs::copy("test_data/sjis_ja.txt", &in_path)?;
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+    let in_file = std::fs::File::options().write(true).open(&in_path)?;
+    in_file.set_times(std::fs::FileTimes::new().set_accessed(modified).set_modified(modified))?;
+    in_file.set_permissions(std::fs::Permissions::from_mode(0o750))?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_path).arg("-o").arg(&out_dir).arg("--preserve").assert().success();
+    let metadata = std::fs::metadata(out_dir.join("sjis_ja.txt"))?;
+    assert_eq!(0o750, metadata.permissions().mode() & 0o777);
+    assert_eq!(modified, metadata.modified()?);
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn symlinks() -> Result<(), Box<dyn std::error::Error>> {
+    let in_dir = std::path::PathBuf::from("output/symlinks");
+    let out_dir = std::path::PathBuf::from("output/symlinks_out");
+    let _ = std::fs::remove_dir_all(&in_dir);
+    let _ = std::fs::remove_dir_all(&out_dir);
+    std::fs::create_dir_all(in_dir.join("sub"))?;
+    std::fs::create_dir_all(&out_dir)?;
+    std::fs::copy("test_data/sjis_ja.txt", in_dir.join("sjis_ja.txt"))?;
+    std::os::unix::fs::symlink("sjis_ja.txt", in_dir.join("link.txt"))?;
+    std::os::unix::fs::symlink("..", in_dir.join("sub/loop"))?;
+    let loop_error = "output/symlinks/sub/loop: Error following a symbolic link loop. Cause: The directory is its own ancestor\n";
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_dir).arg("-o").arg(&out_dir).assert().code(exitcode::IOERR).stderr(loop_error);
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(out_dir.join("symlinks/sjis_ja.txt"))); // the loop doesn't stop the others
+    let _ = std::fs::remove_dir_all(&out_dir);
+    std::fs::create_dir_all(&out_dir)?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg(&in_dir).arg("-o").arg(&out_dir).arg("--keep-going").assert().code(exitcode::IOERR);
+    let stderr = std::str::from_utf8(&assert.get_output().stderr)?;
+    assert!(stderr.ends_with(&format!("1 failed\n  {}", loop_error)), "{}", stderr);
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(out_dir.join("symlinks/link.txt")));
+    std::os::unix::fs::symlink("../sjis_ja.txt", in_dir.join("sub/link.txt"))?;
+    std::os::unix::fs::symlink(in_dir.canonicalize()?.join("sjis_ja.txt"), in_dir.join("sub/absolute.txt"))?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg(&in_dir).arg("-o").arg(&out_dir).arg("--recreate-symlinks").arg("--dry-run").assert().success();
+    let stdout = std::str::from_utf8(&assert.get_output().stdout)?;
+    assert!(stdout.contains("output/symlinks/sub/link.txt: symbolic link to ../sjis_ja.txt, output/symlinks_out/symlinks/sub/link.txt (create)\n"), "{}", stdout);
+    let _ = std::fs::remove_dir_all(&out_dir);
+    std::fs::create_dir_all(&out_dir)?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_dir).arg("-o").arg(&out_dir).arg("--recreate-symlinks").assert().success().stderr("");
+    assert_eq!(std::path::PathBuf::from("sjis_ja.txt"), std::fs::read_link(out_dir.join("symlinks/link.txt"))?);
+    assert_eq!(std::path::PathBuf::from(".."), std::fs::read_link(out_dir.join("symlinks/sub/loop"))?);
+    // an absolute target in the input root points to the output root
+    assert_eq!(std::path::PathBuf::from("../sjis_ja.txt"), std::fs::read_link(out_dir.join("symlinks/sub/absolute.txt"))?);
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(out_dir.join("symlinks/sub/absolute.txt")));
+    let _ = std::fs::remove_dir_all(&out_dir);
+    std::fs::create_dir_all(&out_dir)?;
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_dir).arg("-o").arg(&out_dir).arg("--no-follow-symlinks").assert().success().stderr("");
+    assert!(! out_dir.join("symlinks/link.txt").exists());
+    assert!(out_dir.join("symlinks/sjis_ja.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn archive() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let in_dir = std::path::PathBuf::from("output/archive/in");
+    let out_dir = std::path::PathBuf::from("output/archive/out");
+    let _ = std::fs::remove_dir_all("output/archive");
+    std::fs::create_dir_all(&in_dir)?;
+    std::fs::create_dir_all(&out_dir)?;
+    let mut zip_writer = zip::ZipWriter::new(std::fs::File::create(in_dir.join("data.zip"))?);
+    let options = zip::write::SimpleFileOptions::default().unix_permissions(0o640);
+    zip_writer.start_file("sub/sjis_ja.txt", options)?;
+    zip_writer.write_all(&read_file("test_data/sjis_ja.txt"))?;
+    zip_writer.start_file("binary.jpeg", options.compression_method(zip::CompressionMethod::Stored))?;
+    zip_writer.write_all(&read_file("test_data/binary.jpeg"))?;
+    zip_writer.finish()?;
+    let encoder = flate2::write::GzEncoder::new(std::fs::File::create(in_dir.join("data.tar.gz"))?, flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, mode) in [("euc-jp_ja.txt", 0o600), ("binary.jpeg", 0o644)] {
+        let content = read_file(std::path::Path::new("test_data").join(name));
+        let mut header = tar::Header::new_ustar();
+        header.set_size(content.len() as u64);
+        header.set_mode(mode);
+        header.set_mtime(1_000_000_000);
+        builder.append_data(&mut header, format!("sub/{}", name), &content[..])?;
+    }
+    builder.into_inner()?.finish()?;
+
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg(in_dir.join("data.zip")).arg("-o").arg(&out_dir).arg("--archive").arg("rewrite").arg("--dry-run").assert();
+    assert.success().stdout("output/archive/in/data.zip: zip archive -> entries rewritten, output/archive/out/data.zip (create)\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_dir).arg("-o").arg(&out_dir).arg("--archive").arg("rewrite").assert().success();
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(out_dir.join("in/data.zip"))?)?;
+    let mut content = Vec::new();
+    archive.by_name("sub/sjis_ja.txt")?.read_to_end(&mut content)?;
+    assert_eq!(read_file("test_data/utf8_ja.txt"), content);
+    assert_eq!(Some(0o100640), archive.by_name("sub/sjis_ja.txt")?.unix_mode());
+    let mut content = Vec::new();
+    archive.by_name("binary.jpeg")?.read_to_end(&mut content)?;
+    assert_eq!(read_file("test_data/binary.jpeg"), content);
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(std::fs::File::open(out_dir.join("in/data.tar.gz"))?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        let path = entry.path()?.into_owned();
+        if path == std::path::Path::new("sub/euc-jp_ja.txt") {
+            assert_eq!(read_file("test_data/utf8_ja.txt"), content);
+            assert_eq!((0o600, 1_000_000_000), (entry.header().mode()?, entry.header().mtime()?));
+        } else {
+            assert_eq!(read_file("test_data/binary.jpeg"), content);
+        }
+    }
+
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_dir).arg("-o").arg(&out_dir).arg("--archive").arg("extract").assert().success();
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(out_dir.join("in/data.zip.d/sub/sjis_ja.txt")));
+    assert_eq!(read_file("test_data/utf8_ja.txt"), read_file(out_dir.join("in/data.tar.d/sub/euc-jp_ja.txt")));
+    assert_eq!(read_file("test_data/binary.jpeg"), read_file(out_dir.join("in/data.zip.d/binary.jpeg")));
+    let modified = std::fs::metadata(out_dir.join("in/data.tar.d/sub/euc-jp_ja.txt"))?.modified()?;
+    assert_eq!(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000), modified);
+
+    let mut cmd = Command::cargo_bin("aconv")?; // archives with the same stem are extracted into different directories
+    let files_dir = out_dir.join("files");
+    cmd.arg(in_dir.join("data.zip")).arg(in_dir.join("data.tar.gz")).arg("-o").arg(&files_dir).arg("--archive").arg("extract")
+        .assert().success();
+    assert!(files_dir.join("data.zip.d/sub/sjis_ja.txt").is_file());
+    assert!(!files_dir.join("data.zip.d/sub/euc-jp_ja.txt").exists());
+    assert!(files_dir.join("data.tar.d/sub/euc-jp_ja.txt").is_file());
+
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(in_dir.join("data.zip")).arg("--archive").arg("extract").assert().code(exitcode::USAGE);
+    Ok(())
+}
+
+#[test]
+fn archive_tar_entries() -> Result<(), Box<dyn std::error::Error>> {
+    let in_dir = std::path::PathBuf::from("output/archive_tar/in");
+    let out_dir = std::path::PathBuf::from("output/archive_tar/out");
+    let _ = std::fs::remove_dir_all("output/archive_tar");
+    std::fs::create_dir_all(&in_dir)?;
+    std::fs::create_dir_all(&out_dir)?;
+    let mut builder = tar::Builder::new(std::fs::File::create(in_dir.join("data.tar"))?);
+    let id3_text = [b"ID3 tags\n".as_ref(), &read_file("test_data/sjis_ja.txt")].concat(); // a text starting with a magic number
+    let pax_text = read_file("test_data/sjis_ja.txt");
+    for (name, content, pax) in [("id3.txt", &id3_text, false), ("pax.txt", &pax_text, true)] {
+        if pax {
+            builder.append_pax_extensions([("SCHILY.xattr.user.test", b"value".as_ref())])?;
+        }
+        let mut header = tar::Header::new_ustar();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, name, &content[..])?;
+    }
+    builder.into_inner()?;
+
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(in_dir.join("data.tar")).arg("-o").arg(&out_dir).arg("--archive").arg("rewrite").assert().success();
+    let mut archive = tar::Archive::new(std::fs::File::open(out_dir.join("data.tar"))?);
+    let mut paths = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let records: Vec<(String, Vec<u8>)> = match entry.pax_extensions()? {
+            Some(extensions) => extensions.map(|e| e.map(|e| (e.key().unwrap().to_owned(), e.value_bytes().to_owned()))).collect::<Result<_, _>>()?,
+            None => vec![],
+        };
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        if path == "id3.txt" {
+            assert_eq!([b"ID3 tags\n".as_ref(), &read_file("test_data/utf8_ja.txt")].concat(), content);
+            assert!(records.is_empty());
+        } else {
+            assert_eq!(read_file("test_data/utf8_ja.txt"), content);
+            assert_eq!(vec![("SCHILY.xattr.user.test".to_string(), b"value".to_vec())], records); // the PAX header is kept
+        }
+        paths.push(path);
+    }
+    assert_eq!(vec!["id3.txt", "pax.txt"], paths);
+    Ok(())
+}
+
+#[test]
+fn compressed() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    let in_dir = std::path::PathBuf::from("output/compressed/in");
+    let out_dir = std::path::PathBuf::from("output/compressed/out");
+    let _ = std::fs::remove_dir_all("output/compressed");
+    std::fs::create_dir_all(&in_dir)?;
+    std::fs::create_dir_all(&out_dir)?;
+    let input = read_file("test_data/sjis_ja.txt");
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&input)?;
+    std::fs::write(in_dir.join("sjis_ja.txt.gz"), encoder.finish()?)?;
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    encoder.write_all(&input)?;
+    std::fs::write(in_dir.join("sjis_ja.txt.bz2"), encoder.finish()?)?;
+    let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+    encoder.write_all(&input)?;
+    std::fs::write(in_dir.join("sjis_ja.txt.xz"), encoder.finish()?)?;
+    std::fs::write(in_dir.join("sjis_ja.txt.zst"), zstd::encode_all(&input[..], 0)?)?;
+
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(&in_dir).arg("-o").arg(&out_dir).assert().success();
+    let expected = read_file("test_data/utf8_ja.txt");
+    let mut output = Vec::new();
+    flate2::read::GzDecoder::new(&read_file(out_dir.join("in/sjis_ja.txt.gz"))[..]).read_to_end(&mut output)?;
+    assert_eq!(expected, output);
+    let mut output = Vec::new();
+    bzip2::read::BzDecoder::new(&read_file(out_dir.join("in/sjis_ja.txt.bz2"))[..]).read_to_end(&mut output)?;
+    assert_eq!(expected, output);
+    let mut output = Vec::new();
+    xz2::read::XzDecoder::new(&read_file(out_dir.join("in/sjis_ja.txt.xz"))[..]).read_to_end(&mut output)?;
+    assert_eq!(expected, output);
+    assert_eq!(expected, zstd::decode_all(&read_file(out_dir.join("in/sjis_ja.txt.zst"))[..])?);
+
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.arg(in_dir.join("sjis_ja.txt.zst")).arg("-o").arg(&out_dir).arg("--compressed=decompress").arg("--dry-run").assert();
+    assert.success().stdout("output/compressed/in/sjis_ja.txt.zst: Shift_JIS -> UTF-8, output/compressed/out/sjis_ja.txt (create), decompressed from zstd\n");
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg(in_dir.join("sjis_ja.txt.zst")).arg("-o").arg(&out_dir).arg("--compressed=decompress").assert().success();
+    assert_eq!(expected, read_file(out_dir.join("sjis_ja.txt")));
+
+    let mut cmd = Command::cargo_bin("aconv")?;
+    let assert = cmd.write_stdin(read_file(in_dir.join("sjis_ja.txt.gz"))).assert().success();
+    let mut output = Vec::new();
+    flate2::read::GzDecoder::new(&assert.get_output().stdout[..]).read_to_end(&mut output)?;
+    assert_eq!(expected, output);
+    let mut cmd = Command::cargo_bin("aconv")?;
+    cmd.arg("--compressed=binary").write_stdin(read_file(in_dir.join("sjis_ja.txt.gz"))).assert().success().stdout("");
+    Ok(())
+}
+
 #[test]
 fn version() -> Result<(), Box<dyn std::error::Error>> {
     let mut cmd = Command::cargo_bin("aconv")?;
@@ -143,6 +805,13 @@ fn error_guess_dir() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn read_file<P: AsRef<std::path::Path>>(path: P) -> Vec<u8> {
+    let mut f = std::fs::File::open(path).unwrap();
+    let mut bytes = Vec::new();
+    f.read_to_end(&mut bytes).unwrap();
+    return bytes;
+}
+
 fn assert_directories(left_str: &str, right_str: &str) {
     let left_root = std::path::PathBuf::from(left_str);
     let right_root = std::path::PathBuf::from(right_str);
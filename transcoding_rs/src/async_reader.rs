@@ -0,0 +1,312 @@
+//! Asynchronous reading with tokio, which is enabled by the `tokio` feature.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::Read;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use crate::I18nReader;
+use crate::I18nReaderEncodingDetector;
+use crate::GuessResult;
+use crate::Transcoder;
+use crate::Detection;
+use crate::Encoding;
+use crate::Eol;
+use crate::Bom;
+
+/// The size of the chunks read from the underlying reader at a time.
+const CHUNK_SIZE: usize = 8*1024;
+
+/// The result of the asynchronous encoding detection, which is the same as `GuessResult`.
+pub enum AsyncGuessResult<R: AsyncRead + Unpin> {
+    /// The input was empty.
+    NoInput,
+    /// The guess succeeded.
+    Success(AsyncI18nReader<R>, Encoding),
+    /// The guess failed.
+    /// In case the input still needs to be read without transcoding,
+    /// this holds an AsyncI18nReader.
+    Fail(AsyncI18nReader<R>),
+}
+
+impl I18nReaderEncodingDetector {
+    /// Guesses the source encoding from an `AsyncRead` and return `AsyncGuessResult`,
+    /// setting the destination encoding to UTF-8.
+    pub async fn guess_utf8_async<R>(self: Self, reader: R)
+        -> io::Result<AsyncGuessResult<R>>
+        where R: AsyncRead + Unpin {
+        return self.guess_async(reader, encoding_rs::UTF_8).await;
+    }
+
+    /// Guesses the source encoding from an `AsyncRead` and return `AsyncGuessResult`,
+    /// setting the destination encoding to the specified one.
+    /// The input is read in the same way as `guess()`, so the results are the same for the same input.
+    ///
+    /// # Example
+    /// ```
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// use tokio::io::AsyncReadExt;
+    ///
+    /// let src = b"\x83\x6E\x83\x8D\x81\x5B"; // ハロー in SHIFT_JIS
+    /// let detector = transcoding_rs::I18nReaderEncodingDetector::new();
+    /// let guess_result = detector.guess_async(src.as_ref(), encoding_rs::EUC_JP).await.unwrap();
+    /// match guess_result {
+    ///     transcoding_rs::AsyncGuessResult::Success(mut reader, enc) => {
+    ///         assert_eq!(encoding_rs::SHIFT_JIS, enc);
+    ///         let buf = &mut Vec::new();
+    ///         reader.read_to_end(buf).await.unwrap();
+    ///         assert_eq!(b"\xA5\xCF\xA5\xED\xA1\xBC", &buf[..]); // ハロー in EUC_JP
+    ///     },
+    ///     _ => panic!()
+    /// }
+    /// # }
+    /// ```
+    pub async fn guess_async<R, D>(self: Self, mut reader: R, dst_encoding: D)
+        -> io::Result<AsyncGuessResult<R>>
+        where R: AsyncRead + Unpin, D: Into<Encoding> {
+        // the head is handed to guess() through the feed, so that its two reads get the same bytes as from a slice.
+        // an async reader tends to return less than requested, so the first part is filled as far as possible.
+        let mut feed = Feed::default();
+        let read_buf = &mut vec![0u8; self.bytes_to_guess];
+        let buf_minus1 = read_buf.len()-1;
+        let mut first = 0;
+        while first < buf_minus1 {
+            match reader.read(&mut read_buf[first..buf_minus1]).await? {
+                0 => break,
+                n => first += n,
+            }
+        }
+        feed.push(&read_buf[..first]);
+        if first > 0 {
+            let second = reader.read(&mut read_buf[buf_minus1..]).await?;
+            feed.push(&read_buf[buf_minus1..buf_minus1+second]);
+        }
+        return Ok(match self.guess(feed, dst_encoding)? {
+            GuessResult::NoInput => AsyncGuessResult::NoInput,
+            GuessResult::Success(i18n_reader, enc) => AsyncGuessResult::Success(AsyncI18nReader::from_reader(reader, i18n_reader), enc),
+            GuessResult::Fail(i18n_reader) => AsyncGuessResult::Fail(AsyncI18nReader::from_reader(reader, i18n_reader)),
+        });
+    }
+}
+
+/// Asynchronous version of `I18nReader`, which transcodes an `AsyncRead` on the fly.
+///
+/// # Example
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use tokio::io::AsyncReadExt;
+///
+/// let transcoder = transcoding_rs::Transcoder::new(Some(encoding_rs::SHIFT_JIS), encoding_rs::UTF_8);
+/// let src = b"\x83\x6E\x83\x8D\x81\x5B"; // ハロー in SHIFT_JIS
+/// let mut reader = transcoding_rs::AsyncI18nReader::new(src.as_ref(), transcoder);
+/// let buf = &mut String::new();
+/// reader.read_to_string(buf).await.unwrap();
+/// assert_eq!("ハロー", buf);
+/// # }
+/// ```
+pub struct AsyncI18nReader<R: AsyncRead + Unpin> {
+    reader: R,
+    /// The synchronous reader that does the work, which reads the input from the feed.
+    i18n_reader: I18nReader<Feed>,
+    chunk: Vec<u8>,
+}
+
+impl <R: AsyncRead + Unpin> AsyncI18nReader<R> {
+    /// Creates a new `AsyncI18nReader`.
+    /// `Transcoder` needs to have the source encoding.
+    /// If the source encoding can't be known in advance,
+    /// use `I18nReaderEncodingDetector::guess_async()`.
+    pub fn new(reader: R, transcoder: Transcoder) -> Self {
+        return Self::from_reader(reader, I18nReader::new(Feed::default(), transcoder));
+    }
+
+    fn from_reader(reader: R, i18n_reader: I18nReader<Feed>) -> Self {
+        return Self { reader, i18n_reader, chunk: vec![0u8; CHUNK_SIZE] };
+    }
+
+    /// Sets how to convert line endings. See `I18nReader::eol()` for details.
+    pub fn eol(mut self: Self, eol: Eol) -> Self {
+        self.i18n_reader = self.i18n_reader.eol(eol);
+        return self;
+    }
+
+    /// Sets how to handle a BOM at the head of the output. See `I18nReader::bom()` for details.
+    pub fn bom(mut self: Self, bom: Bom) -> Self {
+        self.i18n_reader = self.i18n_reader.bom(bom);
+        return self;
+    }
+
+    /// Whether replacement characters or unmappable characters are seen so far.
+    pub fn had_replacement_or_unmappable(self: &Self) -> bool {
+        return self.i18n_reader.had_replacement_or_unmappable();
+    }
+
+    /// Whether the input starts with a BOM. See `I18nReader::had_bom()` for details.
+    pub fn had_bom(self: &Self) -> bool {
+        return self.i18n_reader.had_bom();
+    }
+
+    /// The number of bytes examined to guess the encoding. See `I18nReader::bytes_guessed()` for details.
+    pub fn bytes_guessed(self: &Self) -> usize {
+        return self.i18n_reader.bytes_guessed();
+    }
+
+    /// The details of the encoding detection. See `I18nReader::detection()` for details.
+    pub fn detection(self: &Self) -> Option<&Detection> {
+        return self.i18n_reader.detection();
+    }
+}
+
+impl <R: AsyncRead + Unpin> AsyncRead for AsyncI18nReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            match this.i18n_reader.read(buf.initialize_unfilled()) {
+                Ok(n) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}, // the feed ran out
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+            let chunk = &mut ReadBuf::new(&mut this.chunk);
+            match Pin::new(&mut this.reader).poll_read(cx, chunk) {
+                Poll::Ready(Ok(())) => this.i18n_reader.reader.push(chunk.filled()),
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// The input of the synchronous reader, which is fed with the chunks read asynchronously.
+/// A chunk is returned by a read as the underlying reader returned it, and an empty chunk means the end of the input.
+/// Reading returns an error of the `WouldBlock` kind if no chunks are left.
+#[derive(Debug, Default)]
+struct Feed {
+    chunks: VecDeque<Vec<u8>>,
+    eof: bool,
+}
+
+impl Feed {
+    fn push(self: &mut Self, chunk: &[u8]) {
+        self.chunks.push_back(chunk.into());
+    }
+}
+
+impl Read for Feed {
+    fn read(self: &mut Self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.eof {
+            return Ok(0);
+        }
+        let chunk = match self.chunks.front_mut() {
+            Some(chunk) => chunk,
+            None => return Err(io::ErrorKind::WouldBlock.into()),
+        };
+        if chunk.is_empty() {
+            self.eof = true;
+            self.chunks.pop_front();
+            return Ok(0);
+        }
+        let n = std::cmp::min(buffer.len(), chunk.len());
+        buffer[..n].copy_from_slice(&chunk[..n]);
+        chunk.drain(..n);
+        if chunk.is_empty() {
+            self.chunks.pop_front();
+        }
+        return Ok(n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::path;
+    use tokio::io::AsyncReadExt;
+    use super::*;
+
+    /// Returns the input a few bytes at a time, and is pending every other poll.
+    struct Trickle<'a> {
+        input: &'a [u8],
+        pending: bool,
+    }
+
+    impl <'a> AsyncRead for Trickle<'a> {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = std::cmp::min(3, std::cmp::min(buf.remaining(), self.input.len()));
+            buf.put_slice(&self.input[..n]);
+            self.input = &self.input[n..];
+            return Poll::Ready(Ok(()));
+        }
+    }
+
+    macro_rules! async_test {
+        ($name:ident, $input_file:expr, $enc:expr) => {
+            #[tokio::test]
+            async fn $name() {
+                let input = std::fs::read(path::Path::new("../test_data").join($input_file)).unwrap();
+                let dst = encoding_rs::Encoding::for_label($enc.as_bytes()).unwrap();
+                let mut expected = Vec::new();
+                let expected_enc = match I18nReaderEncodingDetector::new().guess(&input[..], dst).unwrap() {
+                    GuessResult::Success(mut reader, enc) => { reader.read_to_end(&mut expected).unwrap(); Some(enc) },
+                    GuessResult::Fail(mut reader) => { reader.read_to_end(&mut expected).unwrap(); None },
+                    GuessResult::NoInput => None,
+                };
+                let trickle = Trickle { input: &input, pending: false };
+                let mut output = Vec::new();
+                let enc = match I18nReaderEncodingDetector::new().guess_async(trickle, dst).await.unwrap() {
+                    AsyncGuessResult::Success(mut reader, enc) => { reader.read_to_end(&mut output).await.unwrap(); Some(enc) },
+                    AsyncGuessResult::Fail(mut reader) => { reader.read_to_end(&mut output).await.unwrap(); None },
+                    AsyncGuessResult::NoInput => None,
+                };
+                assert_eq!(expected_enc, enc);
+                assert_eq!(expected, output);
+            }
+        };
+    }
+
+    async_test!(async_sjis_utf8        , "sjis_ja.txt"        , "utf8");
+    async_test!(async_utf8_euckr       , "utf8_ko.txt"        , "euc-kr");
+    async_test!(async_utf16le_utf8     , "utf16le_BOM_th.txt" , "utf8");
+    async_test!(async_utf8_utf8        , "utf8_th.txt"        , "utf8");
+    async_test!(async_binary           , "binary.jpeg"        , "utf8");
+
+    #[tokio::test]
+    async fn async_empty() {
+        let result = I18nReaderEncodingDetector::new().guess_utf8_async(b"".as_ref()).await.unwrap();
+        assert!(matches!(result, AsyncGuessResult::NoInput));
+    }
+
+    #[tokio::test]
+    async fn async_strict() {
+        let src = b"\x83\x6E\x83\x8D\x81\x5B\x83\x8F\x81\x5B\x83\x8B\x83\x68ab\x84"; // ends with a truncated sequence
+        let f = I18nReaderEncodingDetector::new().bytes_to_guess(16).strict(true);
+        if let AsyncGuessResult::Success(mut reader, _) = f.guess_utf8_async(Trickle { input: src, pending: false }).await.unwrap() {
+            let mut buff = Vec::new();
+            let err = reader.read_to_end(&mut buff).await.unwrap_err();
+            let transcode_err = err.get_ref().unwrap().downcast_ref::<crate::TranscodeError>().unwrap();
+            assert_eq!(&crate::TranscodeError::Malformed { offset: 16 }, transcode_err);
+            assert_eq!("ハローワールドab".as_bytes(), &buff[..]);
+        } else {
+            panic!();
+        }
+    }
+
+    #[tokio::test]
+    async fn async_eol() {
+        let src = b"\x83\x6E\r\n\x83\x8D\r\n"; // ハ and ロ in SHIFT_JIS with CRLF
+        let transcoder = Transcoder::new(Some(encoding_rs::SHIFT_JIS), encoding_rs::UTF_8);
+        let mut reader = AsyncI18nReader::new(Trickle { input: src, pending: false }, transcoder).eol(Eol::Lf);
+        let mut buff = String::new();
+        reader.read_to_string(&mut buff).await.unwrap();
+        assert_eq!("ハ\nロ\n", buff);
+    }
+}
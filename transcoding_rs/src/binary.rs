@@ -0,0 +1,169 @@
+//! Binary file detection.
+
+use crate::constants;
+use crate::encoding::Encoding;
+use crate::transcoder::Transcoder;
+
+/// The percentage of non-text bytes above which the input is considered binary.
+/// Unlike the threshold of the encoding detection, this counts the non-text characters except the null character
+/// in the raw bytes, so that texts with some null bytes are still handed to the encoding detection.
+const NON_TEXT_THRESHOLD: usize = 5;
+
+/// The name reported for binary data with no known magic number, which is the same as the `file` command uses.
+pub const UNKNOWN_FORMAT: &str = "data";
+
+// (offset, magic number, format name)
+// The magic numbers made only of printable ASCII, which texts may start with as well, are followed by the bytes
+// of the structure where they can be, e.g. the version of ID3, or else the head must have binary bytes.
+const MAGIC_NUMBERS: [(usize, &[u8], &str); 33] = [
+    (0, b"\x89PNG\r\n\x1A\n", "PNG"),
+    (0, b"\xFF\xD8\xFF", "JPEG"),
+    (0, b"GIF87a", "GIF"),
+    (0, b"GIF89a", "GIF"),
+    (0, b"II*\x00", "TIFF"),
+    (0, b"MM\x00*", "TIFF"),
+    (0, b"\x00\x00\x01\x00", "ICO"),
+    (0, b"%PDF-", "PDF"),
+    (0, b"PK\x03\x04", "Zip"),
+    (0, b"PK\x05\x06", "Zip"),
+    (0, b"\x1F\x8B", "gzip"),
+    (0, b"\xFD7zXZ\x00", "XZ"),
+    (0, b"\x28\xB5\x2F\xFD", "Zstandard"),
+    (0, b"7z\xBC\xAF\x27\x1C", "7-Zip"),
+    (0, b"Rar!\x1A\x07", "RAR"),
+    (257, b"ustar", "tar"),
+    (0, b"\x7FELF", "ELF"),
+    (0, b"\xFE\xED\xFA\xCE", "Mach-O"),
+    (0, b"\xFE\xED\xFA\xCF", "Mach-O"),
+    (0, b"\xCE\xFA\xED\xFE", "Mach-O"),
+    (0, b"\xCF\xFA\xED\xFE", "Mach-O"),
+    (0, b"\xCA\xFE\xBA\xBE", "Mach-O universal or Java class"),
+    (0, b"\x00asm", "WebAssembly"),
+    (0, b"SQLite format 3\x00", "SQLite"),
+    (0, b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", "OLE2 compound document"),
+    (0, b"OggS\x00", "Ogg"),
+    (0, b"fLaC\x00", "FLAC"),
+    (0, b"fLaC\x80", "FLAC"),
+    (0, b"ID3\x02", "MP3"),
+    (0, b"ID3\x03", "MP3"),
+    (0, b"ID3\x04", "MP3"),
+    (4, b"ftyp", "ISO Media"),
+    (0, b"\x1A\x45\xDF\xA3", "Matroska"),
+];
+
+/// Tells if the input is binary data rather than texts, by the head of the input.
+/// Returns the name of the format if the input starts with a known magic number (e.g. "PNG", "ELF"),
+/// "data" (`UNKNOWN_FORMAT`) if the bytes have too many non-text characters, or None if the input looks like texts.
+/// Input starting with a BOM or looking like BOM-less UTF-16 or UTF-32 is considered texts.
+///
+/// ```
+/// use transcoding_rs::binary_format;
+/// assert_eq!(Some("PNG"), binary_format(b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR"));
+/// assert_eq!(Some("data"), binary_format(b"\x01\x02\x03\x04abc"));
+/// assert_eq!(None, binary_format("テキスト".as_bytes()));
+/// assert_eq!(None, binary_format(b"ID3 tags are metadata.\n"));
+/// ```
+pub fn binary_format(head: &[u8]) -> Option<&'static str> {
+    for (offset, magic, format) in MAGIC_NUMBERS.iter() {
+        if head.len() >= offset + magic.len() && &head[*offset..offset+magic.len()] == *magic
+            && (! is_printable(magic) || has_binary_bytes(head)) {
+            return Some(format);
+        }
+    }
+    if is_bzip2(head) {
+        return Some("bzip2");
+    }
+    if let Some(format) = riff_format(head).filter(|_| has_binary_bytes(head)) {
+        return Some(format);
+    }
+    if is_pe(head) {
+        return Some("PE executable");
+    }
+    if head.is_empty() || Encoding::for_bom(head).is_some() || Transcoder::guess_bomless_utf32(head).is_some()
+        || Transcoder::guess_bomless_utf16(head).is_some() {
+        return None;
+    }
+    if has_many_non_texts(head) {
+        return Some(UNKNOWN_FORMAT);
+    }
+    return None;
+}
+
+fn has_many_non_texts(head: &[u8]) -> bool {
+    let non_text_cnt = head.iter().filter(|b| constants::NON_TEXTS.binary_search(&(**b as char)).is_ok()).count();
+    return non_text_cnt * 100 / head.len() > NON_TEXT_THRESHOLD;
+}
+
+fn is_printable(magic: &[u8]) -> bool {
+    return magic.iter().all(|b| b.is_ascii_graphic() || *b == b' ');
+}
+
+fn has_binary_bytes(head: &[u8]) -> bool {
+    return head.contains(&0) || has_many_non_texts(head);
+}
+
+// "BZh" is followed by the block size from '1' to '9', and the magic number of the first block or the end of the stream.
+fn is_bzip2(head: &[u8]) -> bool {
+    if head.len() < 10 || &head[..3] != b"BZh" || ! (b'1'..=b'9').contains(&head[3]) {
+        return false;
+    }
+    return &head[4..10] == b"1AY&SY" || &head[4..10] == b"\x17\x72\x45\x38\x50\x90";
+}
+
+// RIFF containers tell the format at the offset 8.
+fn riff_format(head: &[u8]) -> Option<&'static str> {
+    if head.len() < 12 || &head[..4] != b"RIFF" {
+        return None;
+    }
+    return match &head[8..12] {
+        b"WEBP" => Some("WebP"),
+        b"WAVE" => Some("WAVE"),
+        b"AVI " => Some("AVI"),
+        _ => Some("RIFF"),
+    };
+}
+
+// "MZ" alone is too short to tell, so the PE header the DOS header points to is checked as well.
+fn is_pe(head: &[u8]) -> bool {
+    if head.len() < 0x40 || &head[..2] != b"MZ" {
+        return false;
+    }
+    let pe_offset = u32::from_le_bytes([head[0x3C], head[0x3D], head[0x3E], head[0x3F]]) as usize;
+    return head.len() >= pe_offset + 4 && &head[pe_offset..pe_offset+4] == b"PE\x00\x00";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! binary_test {
+        ($name:ident, $head:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!($expected, binary_format($head));
+            }
+        };
+    }
+
+    binary_test!(binary_jpeg      , &std::fs::read("../test_data/binary.jpeg").unwrap(), Some("JPEG"));
+    binary_test!(binary_tar       , &[vec![0u8; 257], b"ustar\x0000".to_vec()].concat(), Some("tar"));
+    binary_test!(binary_webp      , b"RIFF\x00\x00\x00\x00WEBPVP8 ", Some("WebP"));
+    binary_test!(binary_pe        , &[b"MZ".as_ref(), &[0; 0x3A], &[0x40, 0, 0, 0], b"PE\x00\x00"].concat(), Some("PE executable"));
+    binary_test!(binary_mz_text   , b"MZ is not always an executable.", None);
+    binary_test!(binary_id3       , b"ID3\x04\x00\x00\x00\x00\x01\x00TIT2", Some("MP3"));
+    binary_test!(binary_id3_text  , b"ID3 tags are metadata.\n", None);
+    binary_test!(binary_bzip2     , b"BZh91AY&SY\x4E\xEC\xE8\x36\x00\x00", Some("bzip2"));
+    binary_test!(binary_bzh_text  , b"BZh is the bzip2 magic number.\n", None);
+    binary_test!(binary_pdf       , b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n1 0 obj\n<< /Length 10 >>\nstream\n\x78\x9C\x00\x01", Some("PDF"));
+    binary_test!(binary_pdf_text  , b"%PDF-1.7 is the latest version.\n", None);
+    binary_test!(binary_riff_text , b"RIFF WAVE files are audio.\n", None);
+    binary_test!(binary_non_texts , b"\x01\x02\x03\x10\x11\x12 some strings", Some("data"));
+    binary_test!(binary_empty     , b"", None);
+    binary_test!(binary_nulls     , b"a\x00\x00\x00b\nc", None);
+    binary_test!(binary_stray_null, b"hello world, this is plain text\x00 and more!", None);
+    binary_test!(binary_one_null  , b"\x01\x02\x03\x04\x05\x06\x07\x08\x00\x10\x11\x12\x13\x14\x15\x16", Some("data"));
+    binary_test!(binary_utf16le   , &std::fs::read("../test_data/utf16le_th.txt").unwrap(), None);
+    binary_test!(binary_utf32be   , &std::fs::read("../test_data/utf32be_BOM_th.txt").unwrap(), None);
+    binary_test!(binary_sjis      , &std::fs::read("../test_data/sjis_ja.txt").unwrap(), None);
+    binary_test!(binary_iso2022jp , &std::fs::read("../test_data/iso-2022-jp_ja.txt").unwrap(), None);
+}
@@ -0,0 +1,13 @@
+//! BOM handling of the output.
+
+/// How to handle a BOM at the head of the output.
+/// This takes effect only if the destination encoding is Unicode (UTF-8, UTF-16LE/BE or UTF-32LE/BE).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bom {
+    /// Adds a BOM unless the output already starts with one.
+    Add,
+    /// Removes a BOM at the head of the output.
+    Remove,
+    /// Writes a BOM if and only if the input starts with a BOM.
+    Keep,
+}
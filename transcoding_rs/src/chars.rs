@@ -0,0 +1,319 @@
+//! Iteration over the decoded characters and lines with their byte offsets in the input.
+
+use encoding_rs as enc;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::iter::Peekable;
+use std::str;
+use crate::I18nReader;
+use crate::encoding::{Encoding, Decoder};
+
+// Keeps track of the byte offsets in the input where the characters start.
+// The input consumed by the transcoder is decoded again byte by byte,
+// and the offset of each character decoded is queued in the same order as the transcoder writes it.
+pub(crate) struct OffsetTracker {
+    decoder: Decoder,
+    offsets: VecDeque<usize>,
+    head: Vec<u8>,
+    offset: usize,
+    char_start: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl OffsetTracker {
+    pub fn new(encoding: Encoding) -> Self {
+        return Self {
+            decoder: encoding.new_decoder(),
+            offsets: VecDeque::new(),
+            head: Vec::new(),
+            offset: 0,
+            char_start: 0,
+            started: false,
+            finished: false,
+        };
+    }
+
+    pub fn feed(self: &mut Self, src: &[u8], last: bool) {
+        if self.finished {
+            return;
+        }
+        let decoded = &mut [0u8; 32];
+        for byte in src.chunks(1) {
+            if self.head.len() < 4 {
+                self.head.push(byte[0]);
+            }
+            let (_, _, num_decoded, _) = self.decoder.decode_to_utf8(byte, decoded, false);
+            self.offset += 1;
+            self.push(&decoded[..num_decoded]);
+        }
+        if last {
+            let (_, _, num_decoded, _) = self.decoder.decode_to_utf8(&[], decoded, true);
+            self.push(&decoded[..num_decoded]);
+            self.finished = true;
+        }
+    }
+
+    fn push(self: &mut Self, decoded: &[u8]) {
+        if decoded.is_empty() {
+            return;
+        }
+        if !self.started { // the decoder removes a BOM, which the first character comes after
+            self.started = true;
+            let bom = match self.decoder.encoding() {
+                Encoding::Utf32Le | Encoding::Utf32Be => Encoding::for_bom(&self.head).map(|(_, len)| len),
+                Encoding::Standard(_) => enc::Encoding::for_bom(&self.head).map(|(_, len)| len),
+            };
+            self.char_start = bom.unwrap_or(0);
+        }
+        let num_chars = unsafe { str::from_utf8_unchecked(decoded) }.chars().count();
+        for _ in 0..num_chars {
+            self.offsets.push_back(self.char_start);
+        }
+        self.char_start = self.offset;
+    }
+
+    pub fn pop(self: &mut Self) -> Option<usize> {
+        return self.offsets.pop_front();
+    }
+}
+
+/// Iterator over the decoded characters with the byte offsets in the input where they start.
+/// This is created by `I18nReader::char_indices()`.
+pub struct CharIndices<R: Read> {
+    reader: I18nReader<R>,
+    buffer: Vec<u8>,
+    pos: usize,
+    offset: usize,
+    eof: bool,
+    started: bool,
+    done: bool,
+    error: Option<std::io::Error>,
+}
+
+impl <R: Read> CharIndices<R> {
+    /// The error, if any, is returned first, e.g. for a reader that can't tell the offsets.
+    pub(crate) fn new(reader: I18nReader<R>, error: Option<std::io::Error>) -> Self {
+        return Self { reader, buffer: vec![], pos: 0, offset: 0, eof: false, started: false, done: false, error };
+    }
+
+    /// Gets a reference to the underlying reader, e.g. to see `had_replacement_or_unmappable()`.
+    pub fn get_ref(self: &Self) -> &I18nReader<R> {
+        return &self.reader;
+    }
+
+    // Decodes the first character in the buffer. None if more bytes are needed.
+    fn decode_char(self: &Self) -> std::io::Result<Option<char>> {
+        let rest = &self.buffer[self.pos..];
+        let head = &rest[..std::cmp::min(4, rest.len())];
+        let valid = match str::from_utf8(head) {
+            Ok(valid) => valid,
+            Err(err) if err.valid_up_to() > 0 => str::from_utf8(&head[..err.valid_up_to()]).unwrap(),
+            Err(err) if err.error_len().is_none() && !self.eof => "", // incomplete
+            Err(_) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")),
+        };
+        return Ok(valid.chars().next());
+    }
+
+    fn next_char(self: &mut Self) -> std::io::Result<Option<(usize, char)>> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        if self.reader.transcoder.dst_encoding() != enc::UTF_8 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "the destination encoding is not UTF-8"));
+        }
+        loop {
+            if let Some(c) = self.decode_char()? {
+                self.pos += c.len_utf8();
+                let offset = match self.reader.offset_tracker.as_mut() {
+                    Some(tracker) => match tracker.pop() {
+                        Some(offset) => offset,
+                        None => return Err(std::io::Error::new(std::io::ErrorKind::Other,
+                            "the offsets of the characters are out of sync with the output")),
+                    },
+                    None => self.offset, // read as-is, so the output offset is the input offset
+                };
+                self.offset = offset + c.len_utf8();
+                if !self.started {
+                    self.started = true;
+                    if c == '\u{FEFF}' {
+                        continue; // the BOM kept as-is
+                    }
+                }
+                return Ok(Some((offset, c)));
+            }
+            if self.eof {
+                return Ok(None);
+            }
+            self.buffer.drain(..self.pos);
+            self.pos = 0;
+            let chunk = &mut [0u8; 8*1024];
+            let n = self.reader.read_transcoded(chunk)?;
+            self.buffer.extend_from_slice(&chunk[..n]);
+            self.eof = n == 0;
+        }
+    }
+}
+
+impl <R: Read> Iterator for CharIndices<R> {
+    type Item = std::io::Result<(usize, char)>;
+
+    fn next(self: &mut Self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let rslt = self.next_char().transpose();
+        self.done = !matches!(rslt, Some(Ok(_)));
+        return rslt;
+    }
+}
+
+/// Iterator over the decoded lines with the byte offsets in the input where they start.
+/// The lines don't include the line endings, which are any of CRLF, CR and LF.
+/// This is created by `I18nReader::line_indices()`.
+pub struct LineIndices<R: Read> {
+    chars: Peekable<CharIndices<R>>,
+}
+
+impl <R: Read> LineIndices<R> {
+    pub(crate) fn new(chars: CharIndices<R>) -> Self {
+        return Self { chars: chars.peekable() };
+    }
+}
+
+impl <R: Read> Iterator for LineIndices<R> {
+    type Item = std::io::Result<(usize, String)>;
+
+    fn next(self: &mut Self) -> Option<Self::Item> {
+        let mut line = String::new();
+        let mut start = None;
+        loop {
+            let (offset, c) = match self.chars.next() {
+                Some(Ok(char_index)) => char_index,
+                Some(Err(err)) => return Some(Err(err)),
+                None => return start.map(|start| Ok((start, line))),
+            };
+            let start = *start.get_or_insert(offset);
+            match c {
+                '\n' => return Some(Ok((start, line))),
+                '\r' => {
+                    if let Some(Ok((_, '\n'))) = self.chars.peek() {
+                        self.chars.next();
+                    }
+                    return Some(Ok((start, line)));
+                },
+                _ => line.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::I18nReaderEncodingDetector;
+    use crate::GuessResult;
+    use crate::Transcoder;
+    use crate::Eol;
+
+    fn char_indices(src: &[u8], src_enc: &'static enc::Encoding) -> Vec<(usize, char)> {
+        let reader = I18nReader::new(src, Transcoder::new(Some(src_enc), enc::UTF_8));
+        return reader.char_indices().map(|c| c.unwrap()).collect();
+    }
+
+    #[test]
+    fn chars_sjis() {
+        let src = b"a\x83\x6Eb\x83\x8D"; // aハbロ in SHIFT_JIS
+        assert_eq!(vec![(0, 'a'), (1, 'ハ'), (3, 'b'), (4, 'ロ')], char_indices(src, enc::SHIFT_JIS));
+    }
+
+    #[test]
+    fn chars_utf16le_bom() {
+        let src = b"\xFF\xFEa\x00\xCF\x30"; // aハ in UTF-16LE with a BOM
+        assert_eq!(vec![(2, 'a'), (4, 'ハ')], char_indices(src, enc::UTF_16LE));
+    }
+
+    #[test]
+    fn chars_iso2022jp() {
+        let src = b"a\x1B$B%O\x1B(Bb"; // aハb in ISO-2022-JP
+        assert_eq!(vec![(0, 'a'), (1, 'ハ'), (6, 'b')], char_indices(src, enc::ISO_2022_JP));
+    }
+
+    #[test]
+    fn chars_malformed() {
+        let src = b"a\x83"; // truncated at the end
+        assert_eq!(vec![(0, 'a'), (1, '\u{FFFD}')], char_indices(src, enc::SHIFT_JIS));
+    }
+
+    #[test]
+    fn chars_guess() {
+        let src = "ハローワールド\n".repeat(100);
+        let (sjis, _, _) = enc::SHIFT_JIS.encode(&src);
+        let detector = I18nReaderEncodingDetector::new().bytes_to_guess(100);
+        if let GuessResult::Success(reader, _) = detector.guess_utf8(&sjis[..]).unwrap() {
+            let chars: Vec<(usize, char)> = reader.char_indices().map(|c| c.unwrap()).collect();
+            let mut expected = Vec::new();
+            let mut offset = 0;
+            for c in src.chars() {
+                expected.push((offset, c));
+                offset += enc::SHIFT_JIS.encode(&c.to_string()).0.len();
+            }
+            assert_eq!(expected, chars);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn chars_no_transcoding() {
+        let src = "\u{FEFF}aハ".as_bytes();
+        if let GuessResult::Success(reader, _) = I18nReaderEncodingDetector::new().guess_utf8(src).unwrap() {
+            let chars: Vec<(usize, char)> = reader.char_indices().map(|c| c.unwrap()).collect();
+            assert_eq!(vec![(3, 'a'), (4, 'ハ')], chars);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn chars_non_utf8() {
+        let reader = I18nReader::new(b"a".as_ref(), Transcoder::new(Some(enc::UTF_8), enc::SHIFT_JIS));
+        let chars: Vec<std::io::Result<(usize, char)>> = reader.char_indices().collect();
+        assert_eq!(1, chars.len());
+        assert_eq!(std::io::ErrorKind::InvalidInput, chars[0].as_ref().unwrap_err().kind());
+    }
+
+    #[test]
+    fn chars_unknown_source() {
+        let reader = I18nReader::new(b"a".as_ref(), Transcoder::new(None, enc::UTF_8));
+        let chars: Vec<std::io::Result<(usize, char)>> = reader.char_indices().collect();
+        assert_eq!(1, chars.len());
+        assert_eq!(std::io::ErrorKind::InvalidInput, chars[0].as_ref().unwrap_err().kind());
+    }
+
+    #[test]
+    fn chars_after_read() {
+        let src = b"a\x83\x6Eb"; // aハb in SHIFT_JIS
+        let mut reader = I18nReader::new(src.as_ref(), Transcoder::new(Some(enc::SHIFT_JIS), enc::UTF_8));
+        let mut buff = [0u8; 1];
+        reader.read_exact(&mut buff).unwrap();
+        let chars: Vec<std::io::Result<(usize, char)>> = reader.char_indices().collect();
+        assert_eq!(1, chars.len());
+        assert_eq!(std::io::ErrorKind::InvalidInput, chars[0].as_ref().unwrap_err().kind());
+    }
+
+    #[test]
+    fn lines_sjis() {
+        let src = b"\x83\x6E\r\n\r\x83\x8D\nab"; // ハ, an empty line, ロ and ab in SHIFT_JIS with mixed line endings
+        let reader = I18nReader::new(src.as_ref(), Transcoder::new(Some(enc::SHIFT_JIS), enc::UTF_8)).eol(Eol::Lf);
+        let lines: Vec<(usize, String)> = reader.line_indices().map(|l| l.unwrap()).collect();
+        assert_eq!(vec![(0, "ハ".into()), (4, "".into()), (5, "ロ".into()), (8, "ab".into())], lines);
+    }
+
+    #[test]
+    fn lines_trailing_newline() {
+        let reader = I18nReader::new(b"a\n".as_ref(), Transcoder::new(Some(enc::UTF_8), enc::UTF_8));
+        let lines: Vec<(usize, String)> = reader.line_indices().map(|l| l.unwrap()).collect();
+        assert_eq!(vec![(0, "a".into())], lines);
+    }
+}
@@ -0,0 +1,131 @@
+//! Details of the encoding detection.
+
+use std::fmt;
+
+use crate::constants;
+use crate::encoding::Encoding;
+
+/// The result of the encoding detection with the candidates considered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    /// The detected encoding. None if the guess was rejected.
+    pub encoding: Option<Encoding>,
+    /// Whether the encoding was determined by a BOM.
+    pub bom: bool,
+    /// The candidate encodings ranked by their scores, the best first.
+    /// This is empty if the detection was done without ranking. See `Transcoder::rank_candidates()` for details.
+    /// The detected encoding is the one `chardetng` picked, which is usually but not necessarily the first one.
+    pub candidates: Vec<Candidate>,
+    /// The percentage (0-100) of non-text characters in the input decoded with the guessed encoding.
+    pub non_text_ratio: f64,
+    /// The reason why the guess was rejected.
+    pub rejection: Option<Rejection>,
+}
+
+/// A candidate encoding of the detection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub encoding: Encoding,
+    /// The score between 0 and 1.
+    /// This is a heuristic which reflects how many top-level domain hints `chardetng` picked the encoding with,
+    /// and how few non-text characters are found when the input is decoded with the encoding.
+    pub score: f64,
+    /// The percentage (0-100) of non-text characters in the input decoded with the encoding.
+    pub non_text_ratio: f64,
+}
+
+/// The reason why the guess was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// No characters were decoded.
+    NoText,
+    /// The percentage of non-text characters was above the threshold.
+    NonText { ratio: f64, threshold: u8 },
+}
+
+impl Detection {
+    /// Returns up to `n` best candidates.
+    pub fn top(self: &Self, n: usize) -> &[Candidate] {
+        return &self.candidates[..std::cmp::min(n, self.candidates.len())];
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::NoText => write!(f, "No characters were decoded"),
+            Rejection::NonText { ratio, threshold } =>
+                write!(f, "Non-text characters were {:.1}%, which is above the threshold {}%", ratio, threshold),
+        }
+    }
+}
+
+/// Returns the top-level domain that hints `chardetng` at the language,
+/// which is an ISO 639-1 code optionally followed by a region or a script (e.g. `ja`, `zh-TW`, `zh_Hant`).
+/// Returns None for unknown languages.
+///
+/// # Example
+/// ```
+/// assert_eq!(Some("jp"), transcoding_rs::tld_for_language("ja-JP"));
+/// assert_eq!(Some("tw"), transcoding_rs::tld_for_language("zh-Hant"));
+/// assert_eq!(None, transcoding_rs::tld_for_language("xx"));
+/// ```
+pub fn tld_for_language(language: &str) -> Option<&'static str> {
+    let language = language.to_ascii_lowercase().replace('_', "-");
+    let mut subtags = language.split('-');
+    let primary = subtags.next().unwrap_or("");
+    if primary == "zh" {
+        for subtag in subtags {
+            match subtag {
+                "tw" | "mo" | "hant" => return Some("tw"),
+                "hk" => return Some("hk"),
+                _ => {},
+            }
+        }
+    }
+    return constants::LANGUAGE_TLDS.iter().find(|(l, _)| *l == primary).map(|(_, tld)| *tld);
+}
+
+/// Returns the top-level domain lower-cased and without a leading period, as `chardetng` takes it.
+/// Returns None if it's not a valid top-level domain, since `chardetng` panics on periods or non-ASCII characters.
+///
+/// # Example
+/// ```
+/// assert_eq!(Some("jp".to_string()), transcoding_rs::normalize_tld(".JP"));
+/// assert_eq!(None, transcoding_rs::normalize_tld("co.jp"));
+/// ```
+pub fn normalize_tld(tld: &str) -> Option<String> {
+    let tld = tld.trim_start_matches('.').to_ascii_lowercase();
+    if tld.is_empty() || !tld.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+        return None;
+    }
+    return Some(tld);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_tlds() {
+        assert_eq!(Some("jp"), tld_for_language("ja"));
+        assert_eq!(Some("jp"), tld_for_language("JA_jp"));
+        assert_eq!(Some("cn"), tld_for_language("zh"));
+        assert_eq!(Some("cn"), tld_for_language("zh-Hans-CN"));
+        assert_eq!(Some("tw"), tld_for_language("zh-TW"));
+        assert_eq!(Some("hk"), tld_for_language("zh-HK"));
+        assert_eq!(None, tld_for_language(""));
+        let mut languages: Vec<_> = constants::LANGUAGE_TLDS.iter().map(|(l, _)| l).collect();
+        languages.dedup();
+        assert_eq!(constants::LANGUAGE_TLDS.len(), languages.len());
+    }
+
+    #[test]
+    fn tlds() {
+        assert_eq!(Some("jp".to_string()), normalize_tld(".JP"));
+        assert_eq!(Some("xn--p1ai".to_string()), normalize_tld("xn--p1ai"));
+        assert_eq!(None, normalize_tld("co.jp"));
+        assert_eq!(None, normalize_tld("日本"));
+        assert_eq!(None, normalize_tld(""));
+    }
+}
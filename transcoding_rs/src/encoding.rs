@@ -0,0 +1,385 @@
+//! Encodings including the ones that `encoding_rs` doesn't support.
+
+use encoding_rs as enc;
+use std::fmt;
+
+/// An encoding, which is either one defined in the Encoding Standard or UTF-32.
+/// `encoding_rs` only supports the former, so UTF-32 is implemented by this library.
+///
+/// `&'static encoding_rs::Encoding` can be converted into this type, and the two can be compared with each other.
+///
+/// # Example
+/// ```
+/// use encoding_rs;
+/// use transcoding_rs::Encoding;
+///
+/// assert_eq!(Some(Encoding::Utf32Le), Encoding::for_label(b"utf-32"));
+/// assert_eq!(encoding_rs::SHIFT_JIS, Encoding::for_label(b"sjis").unwrap());
+/// assert_eq!("UTF-32BE", Encoding::Utf32Be.name());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// An encoding defined in the Encoding Standard.
+    Standard(&'static enc::Encoding),
+    Utf32Le,
+    Utf32Be,
+}
+
+impl Encoding {
+    /// Looks up an encoding by a label.
+    /// In addition to the labels defined in the Encoding Standard, `utf-32`, `utf-32le` and `utf-32be` are accepted.
+    /// `utf-32` is treated as UTF-32LE like `utf-16` is treated as UTF-16LE.
+    pub fn for_label(label: &[u8]) -> Option<Encoding> {
+        let trimmed = String::from_utf8_lossy(label).trim().to_ascii_lowercase();
+        return match trimmed.as_str() {
+            "utf-32" | "utf-32le" => Some(Encoding::Utf32Le),
+            "utf-32be" => Some(Encoding::Utf32Be),
+            _ => enc::Encoding::for_label(label).map(Encoding::Standard),
+        };
+    }
+
+    /// Sniffs a BOM at the head of `src`, returning the encoding and the length of the BOM.
+    /// UTF-32LE is checked before UTF-16LE, since the UTF-16LE BOM is a prefix of the UTF-32LE one.
+    pub fn for_bom(src: &[u8]) -> Option<(Encoding, usize)> {
+        if src.starts_with(b"\xFF\xFE\x00\x00") {
+            return Some((Encoding::Utf32Le, 4));
+        } else if src.starts_with(b"\x00\x00\xFE\xFF") {
+            return Some((Encoding::Utf32Be, 4));
+        }
+        return enc::Encoding::for_bom(src).map(|(encoding, len)| (Encoding::Standard(encoding), len));
+    }
+
+    /// The name of the encoding.
+    pub fn name(self: &Self) -> &'static str {
+        return match self {
+            Encoding::Standard(encoding) => encoding.name(),
+            Encoding::Utf32Le => "UTF-32LE",
+            Encoding::Utf32Be => "UTF-32BE",
+        };
+    }
+
+    /// The BOM of the encoding if it is a Unicode encoding.
+    pub fn bom(self: &Self) -> Option<&'static [u8]> {
+        return match self {
+            Encoding::Standard(encoding) if *encoding == enc::UTF_8 => Some(b"\xEF\xBB\xBF"),
+            Encoding::Standard(encoding) if *encoding == enc::UTF_16LE => Some(b"\xFF\xFE"),
+            Encoding::Standard(encoding) if *encoding == enc::UTF_16BE => Some(b"\xFE\xFF"),
+            Encoding::Standard(_) => None,
+            Encoding::Utf32Le => Some(b"\xFF\xFE\x00\x00"),
+            Encoding::Utf32Be => Some(b"\x00\x00\xFE\xFF"),
+        };
+    }
+
+    /// Returns the `encoding_rs` encoding unless this is UTF-32.
+    pub fn as_standard(self: &Self) -> Option<&'static enc::Encoding> {
+        return match self {
+            Encoding::Standard(encoding) => Some(encoding),
+            _ => None,
+        };
+    }
+
+    // Creates a decoder with BOM sniffing.
+    pub(crate) fn new_decoder(self: &Self) -> Decoder {
+        return match self {
+            Encoding::Standard(encoding) => Decoder::Standard(encoding.new_decoder()),
+            Encoding::Utf32Le => Decoder::Utf32(Utf32Decoder::new(false)),
+            Encoding::Utf32Be => Decoder::Utf32(Utf32Decoder::new(true)),
+        };
+    }
+
+    // Creates an encoder. UTF-16 has no encoder, since encoding_rs decodes into UTF-16 directly.
+    pub(crate) fn new_encoder(self: &Self) -> Option<Encoder> {
+        return match self {
+            Encoding::Standard(encoding) if *encoding == enc::UTF_16LE || *encoding == enc::UTF_16BE => None,
+            Encoding::Standard(encoding) => Some(Encoder::Standard(encoding.new_encoder())),
+            Encoding::Utf32Le => Some(Encoder::Utf32 { big_endian: false }),
+            Encoding::Utf32Be => Some(Encoder::Utf32 { big_endian: true }),
+        };
+    }
+}
+
+impl From<&'static enc::Encoding> for Encoding {
+    fn from(encoding: &'static enc::Encoding) -> Self {
+        return Encoding::Standard(encoding);
+    }
+}
+
+impl PartialEq<&'static enc::Encoding> for Encoding {
+    fn eq(&self, other: &&'static enc::Encoding) -> bool {
+        return *self == Encoding::Standard(other);
+    }
+}
+
+impl PartialEq<Encoding> for &'static enc::Encoding {
+    fn eq(&self, other: &Encoding) -> bool {
+        return other == self;
+    }
+}
+
+impl fmt::Debug for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Encoding {{ {} }}", self.name())
+    }
+}
+
+/// Decoder that has the same methods as `encoding_rs::Decoder` used in this library.
+pub(crate) enum Decoder {
+    Standard(enc::Decoder),
+    Utf32(Utf32Decoder),
+}
+
+impl Decoder {
+    pub fn encoding(self: &Self) -> Encoding {
+        return match self {
+            Decoder::Standard(decoder) => Encoding::Standard(decoder.encoding()),
+            Decoder::Utf32(decoder) if decoder.big_endian => Encoding::Utf32Be,
+            Decoder::Utf32(_) => Encoding::Utf32Le,
+        };
+    }
+
+    pub fn max_utf8_buffer_length(self: &Self, byte_length: usize) -> Option<usize> {
+        return match self {
+            Decoder::Standard(decoder) => decoder.max_utf8_buffer_length(byte_length),
+            Decoder::Utf32(decoder) => byte_length.checked_add(decoder.pending_len + 4), // up to 4 bytes per 4 bytes
+        };
+    }
+
+    pub fn decode_to_utf8(self: &mut Self, src: &[u8], dst: &mut [u8], last: bool) -> (enc::CoderResult, usize, usize, bool) {
+        return match self {
+            Decoder::Standard(decoder) => decoder.decode_to_utf8(src, dst, last),
+            Decoder::Utf32(decoder) => {
+                let mut num_written = 0;
+                let (result, num_read, had_replacement) = decoder.decode(src, last, true, &mut |c| {
+                    if dst.len() - num_written < c.len_utf8() {
+                        return false;
+                    }
+                    num_written += c.encode_utf8(&mut dst[num_written..]).len();
+                    return true;
+                });
+                (to_coder_result(result), num_read, num_written, had_replacement)
+            },
+        };
+    }
+
+    pub fn decode_to_utf8_without_replacement(self: &mut Self, src: &[u8], dst: &mut [u8], last: bool) -> (enc::DecoderResult, usize, usize) {
+        return match self {
+            Decoder::Standard(decoder) => decoder.decode_to_utf8_without_replacement(src, dst, last),
+            Decoder::Utf32(decoder) => {
+                let mut num_written = 0;
+                let (result, num_read, _) = decoder.decode(src, last, false, &mut |c| {
+                    if dst.len() - num_written < c.len_utf8() {
+                        return false;
+                    }
+                    num_written += c.encode_utf8(&mut dst[num_written..]).len();
+                    return true;
+                });
+                (result, num_read, num_written)
+            },
+        };
+    }
+
+    pub fn decode_to_utf16(self: &mut Self, src: &[u8], dst: &mut [u16], last: bool) -> (enc::CoderResult, usize, usize, bool) {
+        return match self {
+            Decoder::Standard(decoder) => decoder.decode_to_utf16(src, dst, last),
+            Decoder::Utf32(decoder) => {
+                let mut num_written = 0;
+                let (result, num_read, had_replacement) = decoder.decode(src, last, true, &mut |c| {
+                    if dst.len() - num_written < c.len_utf16() {
+                        return false;
+                    }
+                    num_written += c.encode_utf16(&mut dst[num_written..]).len();
+                    return true;
+                });
+                (to_coder_result(result), num_read, num_written, had_replacement)
+            },
+        };
+    }
+
+    pub fn decode_to_utf16_without_replacement(self: &mut Self, src: &[u8], dst: &mut [u16], last: bool) -> (enc::DecoderResult, usize, usize) {
+        return match self {
+            Decoder::Standard(decoder) => decoder.decode_to_utf16_without_replacement(src, dst, last),
+            Decoder::Utf32(decoder) => {
+                let mut num_written = 0;
+                let (result, num_read, _) = decoder.decode(src, last, false, &mut |c| {
+                    if dst.len() - num_written < c.len_utf16() {
+                        return false;
+                    }
+                    num_written += c.encode_utf16(&mut dst[num_written..]).len();
+                    return true;
+                });
+                (result, num_read, num_written)
+            },
+        };
+    }
+}
+
+fn to_coder_result(result: enc::DecoderResult) -> enc::CoderResult {
+    return match result {
+        enc::DecoderResult::InputEmpty => enc::CoderResult::InputEmpty,
+        _ => enc::CoderResult::OutputFull, // never malformed, since malformed units are replaced
+    };
+}
+
+/// Decoder for UTF-32LE/BE.
+pub(crate) struct Utf32Decoder {
+    big_endian: bool,
+    pending: [u8; 4],
+    pending_len: usize,
+    bom_checked: bool,
+}
+
+impl Utf32Decoder {
+    fn new(big_endian: bool) -> Self {
+        return Utf32Decoder { big_endian, pending: [0u8; 4], pending_len: 0, bom_checked: false };
+    }
+
+    // Decodes `src` passing each character to `push`, which returns false if there is no room for the character.
+    // Code units that are not Unicode scalar values and a truncated unit at the end are malformed.
+    // They are replaced with the REPLACEMENT CHARACTER if `replace` is true, or otherwise reported as `Malformed`.
+    fn decode(self: &mut Self, src: &[u8], last: bool, replace: bool, push: &mut dyn FnMut(char) -> bool)
+        -> (enc::DecoderResult, usize, bool) {
+        let mut num_read = 0;
+        let mut had_replacement = false;
+        loop {
+            let needed = 4 - self.pending_len;
+            let available = src.len() - num_read;
+            if available < needed {
+                if last && self.pending_len + available > 0 { // truncated unit
+                    if replace {
+                        if !push('\u{FFFD}') {
+                            return (enc::DecoderResult::OutputFull, num_read, had_replacement);
+                        }
+                        self.pending_len = 0;
+                        return (enc::DecoderResult::InputEmpty, src.len(), true);
+                    }
+                    let bad = self.pending_len + available;
+                    self.pending_len = 0;
+                    return (enc::DecoderResult::Malformed(bad as u8, 0), src.len(), had_replacement);
+                }
+                self.pending[self.pending_len..self.pending_len+available].copy_from_slice(&src[num_read..]);
+                self.pending_len += available;
+                return (enc::DecoderResult::InputEmpty, src.len(), had_replacement);
+            }
+            let mut unit = self.pending;
+            unit[self.pending_len..].copy_from_slice(&src[num_read..num_read+needed]);
+            let value = if self.big_endian { u32::from_be_bytes(unit) } else { u32::from_le_bytes(unit) };
+            let is_bom = !self.bom_checked && value == 0xFEFF;
+            if !is_bom {
+                match char::from_u32(value) {
+                    Some(c) => if !push(c) {
+                        return (enc::DecoderResult::OutputFull, num_read, had_replacement);
+                    },
+                    None if replace => {
+                        if !push('\u{FFFD}') {
+                            return (enc::DecoderResult::OutputFull, num_read, had_replacement);
+                        }
+                        had_replacement = true;
+                    },
+                    None => {
+                        self.bom_checked = true;
+                        self.pending_len = 0;
+                        return (enc::DecoderResult::Malformed(4, 0), num_read + needed, had_replacement);
+                    },
+                }
+            }
+            self.bom_checked = true;
+            self.pending_len = 0;
+            num_read += needed;
+        }
+    }
+}
+
+/// Encoder that has the same methods as `encoding_rs::Encoder` used in this library.
+pub(crate) enum Encoder {
+    Standard(enc::Encoder),
+    Utf32 { big_endian: bool },
+}
+
+impl Encoder {
+    pub fn encode_from_utf8(self: &mut Self, src: &str, dst: &mut [u8], last: bool) -> (enc::CoderResult, usize, usize, bool) {
+        return match self {
+            Encoder::Standard(encoder) => encoder.encode_from_utf8(src, dst, last),
+            Encoder::Utf32 { .. } => {
+                let (result, num_read, num_written) = self.encode_from_utf8_without_replacement(src, dst, last);
+                let result = if result == enc::EncoderResult::InputEmpty { enc::CoderResult::InputEmpty } else { enc::CoderResult::OutputFull };
+                (result, num_read, num_written, false)
+            },
+        };
+    }
+
+    pub fn encode_from_utf8_without_replacement(self: &mut Self, src: &str, dst: &mut [u8], last: bool) -> (enc::EncoderResult, usize, usize) {
+        return match self {
+            Encoder::Standard(encoder) => encoder.encode_from_utf8_without_replacement(src, dst, last),
+            Encoder::Utf32 { big_endian } => { // every character is mappable in UTF-32
+                let mut num_read = 0;
+                let mut num_written = 0;
+                for c in src.chars() {
+                    if dst.len() - num_written < 4 {
+                        return (enc::EncoderResult::OutputFull, num_read, num_written);
+                    }
+                    let bytes = if *big_endian { (c as u32).to_be_bytes() } else { (c as u32).to_le_bytes() };
+                    dst[num_written..num_written+4].copy_from_slice(&bytes);
+                    num_read += c.len_utf8();
+                    num_written += 4;
+                }
+                (enc::EncoderResult::InputEmpty, num_read, num_written)
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf32_decoder() {
+        let mut decoder = Encoding::Utf32Be.new_decoder();
+        let dst = &mut [0u8; 32];
+        // BOM, 'a' and '😀' split across the calls
+        let (result, num_read, num_written, _) = decoder.decode_to_utf8(b"\x00\x00\xFE\xFF\x00\x00\x00a\x00\x01", dst, false);
+        assert_eq!((enc::CoderResult::InputEmpty, 10), (result, num_read));
+        assert_eq!(b"a", &dst[..num_written]);
+        let (_, _, num_written, _) = decoder.decode_to_utf8(b"\xF6\x00", dst, true);
+        assert_eq!("😀".as_bytes(), &dst[..num_written]);
+
+        let mut decoder = Encoding::Utf32Le.new_decoder();
+        let (_, _, num_written, had_replacement) = decoder.decode_to_utf8(b"a\x00\x00\x00\x00\xD8\x00\x00b\x00", dst, true);
+        assert!(had_replacement);
+        assert_eq!("a\u{FFFD}\u{FFFD}".as_bytes(), &dst[..num_written]);
+
+        let mut decoder = Encoding::Utf32Le.new_decoder();
+        let (result, num_read, num_written) = decoder.decode_to_utf8_without_replacement(b"a\x00\x00\x00\x00\x00\x11\x00", dst, true);
+        assert_eq!((enc::DecoderResult::Malformed(4, 0), 8), (result, num_read));
+        assert_eq!(b"a", &dst[..num_written]);
+    }
+
+    #[test]
+    fn utf32_decoder_output_full() {
+        let mut decoder = Encoding::Utf32Le.new_decoder();
+        let dst = &mut [0u16; 2];
+        let src = "a😀".chars().flat_map(|c| (c as u32).to_le_bytes()).collect::<Vec<u8>>();
+        let (result, num_read, num_written, _) = decoder.decode_to_utf16(&src, dst, true);
+        assert_eq!((enc::CoderResult::OutputFull, 4, 1), (result, num_read, num_written));
+        let (result, num_read, num_written, _) = decoder.decode_to_utf16(&src[4..], dst, true);
+        assert_eq!((enc::CoderResult::InputEmpty, 4, 2), (result, num_read, num_written));
+    }
+
+    #[test]
+    fn utf32_encoder() {
+        let mut encoder = Encoding::Utf32Be.new_encoder().unwrap();
+        let dst = &mut [0u8; 6];
+        let (result, num_read, num_written) = encoder.encode_from_utf8_without_replacement("a😀", dst, true);
+        assert_eq!((enc::EncoderResult::OutputFull, 1, 4), (result, num_read, num_written));
+        assert_eq!(b"\x00\x00\x00a", &dst[..4]);
+        assert!(Encoding::Standard(enc::UTF_16LE).new_encoder().is_none());
+    }
+
+    #[test]
+    fn boms() {
+        assert_eq!(Some((Encoding::Utf32Le, 4)), Encoding::for_bom(b"\xFF\xFE\x00\x00a"));
+        assert_eq!(Some((Encoding::Standard(enc::UTF_16LE), 2)), Encoding::for_bom(b"\xFF\xFEa\x00"));
+        assert_eq!(Some((Encoding::Utf32Be, 4)), Encoding::for_bom(b"\x00\x00\xFE\xFF"));
+        assert_eq!(None, Encoding::for_bom(b"abc"));
+    }
+}
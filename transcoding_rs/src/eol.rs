@@ -0,0 +1,147 @@
+//! Line ending normalization.
+
+use encoding_rs as enc;
+
+use crate::encoding::Encoding;
+
+/// How to convert line endings (CRLF, CR and LF).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eol {
+    /// Keeps line endings as they are.
+    Keep,
+    /// Converts line endings to LF.
+    Lf,
+    /// Converts line endings to CRLF.
+    Crlf,
+    /// Converts line endings to CR.
+    Cr,
+}
+
+impl Default for Eol {
+    fn default() -> Self {
+        return Eol::Keep;
+    }
+}
+
+impl Eol {
+    fn chars(self: &Self) -> &'static [u32] {
+        return match self {
+            Eol::Keep | Eol::Lf => &[0x0A],
+            Eol::Crlf => &[0x0D, 0x0A],
+            Eol::Cr => &[0x0D],
+        };
+    }
+}
+
+// Converts line endings in the output of the transcoder.
+// Since CR and LF never appear in multi-byte sequences in the supported encodings,
+// the conversion works on the code units of the destination encoding, which are 2 bytes in UTF-16 and 4 bytes in UTF-32.
+// A CR at the end of the input is kept pending until the next unit tells whether it is a part of CRLF.
+#[derive(Debug)]
+pub(crate) struct EolConverter {
+    eol: Eol,
+    unit_len: usize,
+    big_endian: bool,
+    pending: Vec<u8>,
+    pending_cr: bool,
+}
+
+impl EolConverter {
+    pub fn new(eol: Eol, dst_encoding: Encoding) -> Self {
+        let (unit_len, big_endian) = if dst_encoding == enc::UTF_16LE {
+            (2, false)
+        } else if dst_encoding == enc::UTF_16BE {
+            (2, true)
+        } else if dst_encoding == Encoding::Utf32Le {
+            (4, false)
+        } else if dst_encoding == Encoding::Utf32Be {
+            (4, true)
+        } else {
+            (1, false)
+        };
+        return EolConverter { eol, unit_len, big_endian, pending: Vec::new(), pending_cr: false };
+    }
+
+    pub fn convert(self: &mut Self, src: &[u8], last: bool) -> Vec<u8> {
+        let mut input = std::mem::take(&mut self.pending);
+        input.extend_from_slice(src);
+        let num_units = input.len() / self.unit_len;
+        let mut output = Vec::with_capacity(input.len() + input.len() / 8);
+        for unit in input[..num_units*self.unit_len].chunks(self.unit_len) {
+            let value = self.unit_value(unit);
+            if self.pending_cr {
+                self.pending_cr = false;
+                self.write_eol(&mut output);
+                if value == 0x0A { // CRLF
+                    continue;
+                }
+            }
+            match value {
+                0x0D => self.pending_cr = true,
+                0x0A => self.write_eol(&mut output),
+                _ => output.extend_from_slice(unit),
+            }
+        }
+        self.pending = input[num_units*self.unit_len..].to_vec();
+        if last {
+            if self.pending_cr {
+                self.pending_cr = false;
+                self.write_eol(&mut output);
+            }
+            output.append(&mut self.pending); // an incomplete unit is left as it is
+        }
+        return output;
+    }
+
+    fn unit_value(self: &Self, unit: &[u8]) -> u32 {
+        return match (self.unit_len, self.big_endian) {
+            (2, true) => u16::from_be_bytes([unit[0], unit[1]]) as u32,
+            (2, false) => u16::from_le_bytes([unit[0], unit[1]]) as u32,
+            (4, true) => u32::from_be_bytes([unit[0], unit[1], unit[2], unit[3]]),
+            (4, false) => u32::from_le_bytes([unit[0], unit[1], unit[2], unit[3]]),
+            _ => unit[0] as u32,
+        };
+    }
+
+    fn write_eol(self: &Self, output: &mut Vec<u8>) {
+        for c in self.eol.chars() {
+            match (self.unit_len, self.big_endian) {
+                (2, true) => output.extend_from_slice(&(*c as u16).to_be_bytes()),
+                (2, false) => output.extend_from_slice(&(*c as u16).to_le_bytes()),
+                (4, true) => output.extend_from_slice(&c.to_be_bytes()),
+                (4, false) => output.extend_from_slice(&c.to_le_bytes()),
+                _ => output.push(*c as u8),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! eol_test {
+        ($name:ident, $eol:expr, $enc:expr, $src:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let mut converter = EolConverter::new($eol, $enc);
+                let mut output = Vec::new();
+                for chunk in $src.iter() { // each chunk is fed separately
+                    output.append(&mut converter.convert(chunk, false));
+                }
+                output.append(&mut converter.convert(b"", true));
+                assert_eq!($expected.to_vec(), output);
+            }
+        };
+    }
+
+    eol_test!(eol_lf          , Eol::Lf  , enc::UTF_8.into()   , [b"a\r\nb\rc\nd".as_ref()]              , b"a\nb\nc\nd");
+    eol_test!(eol_crlf        , Eol::Crlf, enc::UTF_8.into()   , [b"a\r\nb\rc\nd".as_ref()]              , b"a\r\nb\r\nc\r\nd");
+    eol_test!(eol_cr          , Eol::Cr  , enc::UTF_8.into()   , [b"a\r\nb\rc\nd".as_ref()]              , b"a\rb\rc\rd");
+    eol_test!(eol_split_crlf  , Eol::Lf  , enc::UTF_8.into()   , [b"a\r".as_ref(), b"\nb".as_ref()]      , b"a\nb");
+    eol_test!(eol_trailing_cr , Eol::Crlf, enc::UTF_8.into()   , [b"a\r".as_ref()]                       , b"a\r\n");
+    eol_test!(eol_sjis        , Eol::Lf  , enc::SHIFT_JIS.into(), [b"\x83\x6E\r\n\x83\x8D".as_ref()]     , b"\x83\x6E\n\x83\x8D");
+    eol_test!(eol_utf16le     , Eol::Crlf, enc::UTF_16LE.into(), [b"a\x00\n\x00\r\x00".as_ref()]         , b"a\x00\r\x00\n\x00\r\x00\n\x00");
+    eol_test!(eol_utf16be_split, Eol::Lf , enc::UTF_16BE.into(), [b"\x00a\x00".as_ref(), b"\r\x00".as_ref(), b"\n\x0D\x0A".as_ref()], b"\x00a\x00\n\x0D\x0A");
+    eol_test!(eol_utf32le     , Eol::Lf  , Encoding::Utf32Le   , [b"\r\x00\x00\x00\n\x00".as_ref(), b"\x00\x00".as_ref()], b"\n\x00\x00\x00");
+}
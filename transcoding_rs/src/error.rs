@@ -0,0 +1,37 @@
+//! Errors reported in the strict mode.
+
+use std::fmt;
+
+/// The error that stops transcoding in the strict mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscodeError {
+    /// A malformed byte sequence was found.
+    /// `offset` is the byte offset in the input where the sequence starts.
+    Malformed { offset: usize },
+    /// A character that can't be mapped to the destination encoding was found.
+    /// `offset` is the byte offset in the input where the character starts.
+    Unmappable { offset: usize, character: char },
+}
+
+impl TranscodeError {
+    /// The byte offset in the input where the error was found.
+    pub fn offset(self: &Self) -> usize {
+        match self {
+            TranscodeError::Malformed { offset } => *offset,
+            TranscodeError::Unmappable { offset, .. } => *offset,
+        }
+    }
+}
+
+impl fmt::Display for TranscodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TranscodeError::Malformed { offset } =>
+                write!(f, "Malformed byte sequence found at byte offset {}", offset),
+            TranscodeError::Unmappable { offset, character } =>
+                write!(f, "Unmappable character '{}' (U+{:04X}) found at byte offset {}", character, *character as u32, offset),
+        }
+    }
+}
+
+impl std::error::Error for TranscodeError {}
@@ -1,21 +1,40 @@
 
 use encoding_rs as enc;
 use crate::Transcoder;
+use crate::UnmappablePolicy;
+use crate::Detection;
+use crate::Encoding;
+use crate::Eol;
+use crate::Bom;
+use crate::eol::EolConverter;
+use crate::chars::{OffsetTracker, CharIndices, LineIndices};
 
 /// Encoding detector for I18nReader.
 #[derive(Debug)]
 pub struct I18nReaderEncodingDetector {
-    bytes_to_guess: usize,
+    pub(crate) bytes_to_guess: usize,
     non_ascii_to_guess: usize,
     non_text_threshold: u8,
     buffer: Vec<u8>,
     read_buffer: Vec<u8>,
     write_buffer: Vec<u8>,
+    guessed_len: usize,
     had_replacement_or_unmappable: bool,
     transcode_done: bool,
     eof: bool,
     no_transcoding_needed: bool,
+    had_bom: Option<bool>,
+    bytes_guessed: usize,
+    detection: Option<Detection>,
     add_bom_utf16: bool,
+    strict: bool,
+    unmappable: UnmappablePolicy,
+    allowed_encodings: Vec<Encoding>,
+    tld_hint: Option<String>,
+    bomless_heuristics: bool,
+    rank_candidates: bool,
+    eol: Eol,
+    bom: Option<Bom>,
 }
 
 /// The result of the encoding detection.
@@ -23,7 +42,7 @@ pub enum GuessResult<R: std::io::Read> {
     /// The input was empty.
     NoInput,
     /// The guess_utf8 succeeded.
-    Success(I18nReader<R>, &'static enc::Encoding),
+    Success(I18nReader<R>, Encoding),
     /// The guess_utf8 failed.
     /// In case the input still needs to be read without transcoding,
     /// this holds an I18nReader.
@@ -49,7 +68,40 @@ impl I18nReaderEncodingDetector {
     ///     The value should be specified in percentage.  
     ///     Default is 0%.
     ///  - add_bom_utf16  
-    ///     If it's true and a BOM is not found at the head of the input, a BOM is added.
+    ///     If it's true and a BOM is not found at the head of the input, a BOM is added to UTF-16 and UTF-32 outputs.
+    ///     `bom` takes precedence over this if set.  
+    ///     Default is false.
+    ///  - strict  
+    ///     If it's true, reading stops with an error of the `std::io::ErrorKind::InvalidData` kind
+    ///     at the first malformed byte sequence or unmappable character.
+    ///     The error wraps `TranscodeError`.  
+    ///     Default is false.
+    ///  - unmappable  
+    ///     How to handle characters that can't be mapped to the destination encoding.  
+    ///     If `UnmappablePolicy::Error` is specified, reading stops with an error in the same way as the strict mode.  
+    ///     Default is `UnmappablePolicy::Ncr`.
+    ///  - allowed_encodings  
+    ///     The encodings the detection can pick from.
+    ///     If the guessed encoding is not one of them, the best-scoring one of them is used instead.  
+    ///     Default is empty, which means all encodings are allowed.
+    ///  - tld_hint  
+    ///     The top-level domain passed to `chardetng` as a hint (e.g. `jp`, `ru`, `cz`).  
+    ///     It can also be set by a language (e.g. `ja`) using `language_hint`.  
+    ///     Default is None.
+    ///  - bomless_heuristics  
+    ///     Whether to detect BOM-less UTF-16 and UTF-32 by heuristics. See `Transcoder::bomless_heuristics()` for details.  
+    ///     Default is true.
+    ///  - rank_candidates  
+    ///     Whether to rank the candidates in `I18nReader::detection()`. See `Transcoder::rank_candidates()` for details.  
+    ///     Default is false.
+    ///  - eol  
+    ///     How to convert line endings. See `I18nReader::eol()` for details.
+    ///     This doesn't apply if the guess fails, since the input is read as-is.  
+    ///     Default is `Eol::Keep`.
+    ///  - bom  
+    ///     How to handle a BOM at the head of the output. See `I18nReader::bom()` for details.
+    ///     This doesn't apply if the guess fails, since the input is read as-is.  
+    ///     Default is None, which means a BOM in the input is removed if transcoding is needed, or otherwise kept.
     ///
     /// # Example
     /// ```
@@ -58,7 +110,15 @@ impl I18nReaderEncodingDetector {
     ///     .bytes_to_guess(512)
     ///     .non_ascii_to_guess(10)
     ///     .non_text_threshold(5)
-    ///     .add_bom_utf16(true);
+    ///     .add_bom_utf16(true)
+    ///     .strict(true)
+    ///     .unmappable(transcoding_rs::UnmappablePolicy::Ncr)
+    ///     .allowed_encodings(&[encoding_rs::SHIFT_JIS, encoding_rs::EUC_JP])
+    ///     .tld_hint("jp")
+    ///     .bomless_heuristics(true)
+    ///     .rank_candidates(true)
+    ///     .eol(transcoding_rs::Eol::Lf)
+    ///     .bom(transcoding_rs::Bom::Keep);
     /// ```
     pub fn new() -> Self {
         return Self {
@@ -68,11 +128,23 @@ impl I18nReaderEncodingDetector {
             buffer: vec![0u8; 8*1024],
             read_buffer: vec![],
             write_buffer: vec![],
+            guessed_len: 0,
             had_replacement_or_unmappable: false,
             transcode_done: false,
             eof: false,
             no_transcoding_needed: false,
+            had_bom: None,
+            bytes_guessed: 0,
+            detection: None,
             add_bom_utf16: false,
+            strict: false,
+            unmappable: UnmappablePolicy::Ncr,
+            allowed_encodings: Vec::new(),
+            tld_hint: None,
+            bomless_heuristics: true,
+            rank_candidates: false,
+            eol: Eol::Keep,
+            bom: None,
         };
     }
 
@@ -109,6 +181,65 @@ impl I18nReaderEncodingDetector {
         return self;
     }
 
+    /// Sets strict.
+    pub fn strict(mut self: Self, strict: bool) -> Self {
+        self.strict = strict;
+        return self;
+    }
+
+    /// Sets unmappable.
+    pub fn unmappable(mut self: Self, policy: UnmappablePolicy) -> Self {
+        self.unmappable = policy;
+        return self;
+    }
+
+    /// Sets allowed_encodings.
+    pub fn allowed_encodings<E: Into<Encoding> + Copy>(mut self: Self, encodings: &[E]) -> Self {
+        self.allowed_encodings = encodings.iter().map(|e| (*e).into()).collect();
+        return self;
+    }
+
+    /// Sets tld_hint. See `Transcoder::tld_hint()` for details.
+    pub fn tld_hint(mut self: Self, tld: &str) -> Self {
+        self.tld_hint = Some(tld.into());
+        return self;
+    }
+
+    /// Sets tld_hint by a language. See `Transcoder::language_hint()` for details.
+    pub fn language_hint(mut self: Self, language: &str) -> Result<Self, String> {
+        return match crate::tld_for_language(language) {
+            Some(tld) => {
+                self.tld_hint = Some(tld.into());
+                Ok(self)
+            },
+            None => Err(format!("Unknown language: {}", language)),
+        };
+    }
+
+    /// Sets bomless_heuristics.
+    pub fn bomless_heuristics(mut self: Self, enabled: bool) -> Self {
+        self.bomless_heuristics = enabled;
+        return self;
+    }
+
+    /// Sets rank_candidates.
+    pub fn rank_candidates(mut self: Self, enabled: bool) -> Self {
+        self.rank_candidates = enabled;
+        return self;
+    }
+
+    /// Sets eol.
+    pub fn eol(mut self: Self, eol: Eol) -> Self {
+        self.eol = eol;
+        return self;
+    }
+
+    /// Sets bom.
+    pub fn bom(mut self: Self, bom: Bom) -> Self {
+        self.bom = Some(bom);
+        return self;
+    }
+
     /// Guesses the source encoding and return `GuessResult`,
     /// setting the destination encoding to UTF-8.
     ///
@@ -122,6 +253,7 @@ impl I18nReaderEncodingDetector {
 
     /// Guesses the source encoding and return `GuessResult`,
     /// setting the destination encoding to the specified one.
+    /// The destination encoding can be either `&'static encoding_rs::Encoding` or `Encoding`.
     ///
     /// Once this method is called, the instance is no longer available,
     /// since this method moves the ownership to the return value.
@@ -148,46 +280,80 @@ impl I18nReaderEncodingDetector {
     ///     _ => panic!()
     /// }
     /// ```
-    pub fn guess<R>(mut self: Self, mut reader: R, dst_encoding: &'static enc::Encoding)
+    pub fn guess<R, D>(mut self: Self, mut reader: R, dst_encoding: D)
         -> std::io::Result<GuessResult<R>>
-        where R: std::io::Read {
+        where R: std::io::Read, D: Into<Encoding> {
+        let dst_encoding = dst_encoding.into();
         let read_buf = &mut vec![0u8; self.bytes_to_guess];
         let buf_minus1 =read_buf.len()-1;
-        let first = reader.read(&mut read_buf[..buf_minus1])?;
+        let mut first = 0;
+        let mut eof = false;
+        while first < std::cmp::min(4, buf_minus1) { // a BOM is up to 4 bytes, so it's read as a whole even by short reads
+            let n = reader.read(&mut read_buf[first..buf_minus1])?;
+            if n == 0 {
+                eof = true;
+                break;
+            }
+            first += n;
+        }
         let is_empty = first == 0;
         if is_empty {
             self.eof = true;
             self.transcode_done = true;
             return Ok(GuessResult::NoInput);
         }
-        let second = reader.read(&mut read_buf[buf_minus1..])?;
+        let second = if eof { 0 } else { reader.read(&mut read_buf[first..first+1])? };
         self.eof = second == 0;
         let n = first +second;
         let src = &read_buf[..n];
-        let mut transcoder = Transcoder::new(None, dst_encoding).buffer_size(self.buffer.len());
+        self.had_bom = Some(Encoding::for_bom(src).is_some());
+        self.bytes_guessed = n;
+        let mut transcoder = Transcoder::for_encodings(None, dst_encoding).buffer_size(self.buffer.len()).unmappable(self.unmappable)
+            .allowed_encodings(&self.allowed_encodings)
+            .bomless_heuristics(self.bomless_heuristics)
+            .rank_candidates(self.rank_candidates);
+        if let Some(tld) = &self.tld_hint {
+            transcoder = transcoder.tld_hint(tld);
+        }
         let rslt = transcoder.guess_and_transcode(src, &mut self.buffer, self.non_ascii_to_guess, self.non_text_threshold, self.eof);
-        let (guessed_enc_opt, coder_result, num_read, num_written, has_replacement) = rslt;
+        let (guessed_enc_opt, mut coder_result, mut num_read, mut num_written, mut has_replacement) = rslt;
+        self.detection = transcoder.detection().cloned();
+        let stops_on_error = self.strict || self.unmappable == UnmappablePolicy::Error;
+        if let (Some(guessed_enc), true) = (guessed_enc_opt, stops_on_error) {
+            // the guess doesn't stop on errors, so the input needs to be transcoded again.
+            transcoder = Transcoder::for_encodings(Some(guessed_enc), dst_encoding).buffer_size(self.buffer.len())
+                .strict(self.strict).unmappable(self.unmappable);
+            let rslt = transcoder.transcode(src, &mut self.buffer, self.eof);
+            coder_result = rslt.0;
+            num_read = rslt.1;
+            num_written = rslt.2;
+            has_replacement = rslt.3;
+        }
         self.no_transcoding_needed = guessed_enc_opt.is_none()
-            || (guessed_enc_opt.is_some() && guessed_enc_opt.unwrap() == dst_encoding);
+            || (guessed_enc_opt.is_some() && guessed_enc_opt.unwrap() == dst_encoding && !self.strict);
         if self.no_transcoding_needed {
             self.no_transcoding_needed = true;
             self.write_buffer = src.to_owned();
         } else {
             self.transcode_done = (coder_result == enc::CoderResult::InputEmpty) && self.eof;
             self.had_replacement_or_unmappable = has_replacement;
-            self.read_buffer = src[num_read..].into();
-            self.write_buffer = {
-                if self.add_bom_utf16 && dst_encoding == enc::UTF_16BE && [0xFE,0xFF] != self.buffer[..2] {
-                    [b"\xFE\xFF", &self.buffer[..num_written]].concat() // add a BOM
-                } else if self.add_bom_utf16 && dst_encoding == enc::UTF_16LE && [0xFF,0xFE] != self.buffer[..2] {
-                    [b"\xFF\xFE", &self.buffer[..num_written]].concat() // add a BOM
-                } else{
-                    self.buffer[..num_written].into()
-                }
+            self.read_buffer = src.into();
+            self.guessed_len = num_read;
+            let bom = dst_encoding.bom().filter(|_| self.add_bom_utf16 && dst_encoding != enc::UTF_8);
+            self.write_buffer = match bom {
+                Some(bom) if !self.buffer[..num_written].starts_with(bom) =>
+                    [bom, &self.buffer[..num_written]].concat(), // add a BOM
+                _ => self.buffer[..num_written].into(),
             };
         }
         if let Some(enc) = guessed_enc_opt {
-            return Ok(GuessResult::Success(I18nReader::new_from_factory(reader, transcoder, self), enc));
+            let eol = self.eol;
+            let bom = self.bom;
+            let mut i18n_reader = I18nReader::new_from_factory(reader, transcoder, self).eol(eol);
+            if let Some(bom) = bom {
+                i18n_reader = i18n_reader.bom(bom);
+            }
+            return Ok(GuessResult::Success(i18n_reader, enc));
         } else {
             return Ok(GuessResult::Fail(I18nReader::new_from_factory(reader, transcoder, self)));
         }
@@ -198,15 +364,28 @@ impl I18nReaderEncodingDetector {
 /// Although this is mainly for non-UTF-8,
 /// reads the input source with zero overhead if no transcoding is needed
 pub struct I18nReader<R: std::io::Read> {
-    reader: R,
+    pub(crate) reader: R,
     buffer: Vec<u8>,
     read_buffer: Vec<u8>,
     write_buffer: Vec<u8>,
-    transcoder: Transcoder,
+    guessed_len: usize, // the head of read_buffer consumed by the guess, kept for char_indices()
+    pub(crate) transcoder: Transcoder,
     had_replacement_or_unmappable: bool,
     transcode_done: bool,
     eof: bool,
     no_transcoding_needed: bool,
+    had_bom: Option<bool>,
+    bytes_guessed: usize,
+    detection: Option<Detection>,
+    eol_converter: Option<EolConverter>,
+    bom: Option<Bom>,
+    bom_head: Vec<u8>,
+    output_buffer: Vec<u8>,
+    output_done: bool,
+    fill_buffer: Vec<u8>,
+    fill_pos: usize,
+    pub(crate) offset_tracker: Option<OffsetTracker>,
+    read_started: bool,
 }
 
 impl <R: std::io::Read> I18nReader<R> {
@@ -221,11 +400,24 @@ impl <R: std::io::Read> I18nReader<R> {
             buffer: vec![0u8; 8*1024],
             read_buffer: vec![],
             write_buffer: vec![],
+            guessed_len: 0,
             transcoder,
             had_replacement_or_unmappable: false,
             transcode_done: false,
             eof: false,
             no_transcoding_needed: false,
+            had_bom: None,
+            bytes_guessed: 0,
+            detection: None,
+            eol_converter: None,
+            bom: None,
+            bom_head: vec![],
+            output_buffer: vec![],
+            output_done: false,
+            fill_buffer: vec![],
+            fill_pos: 0,
+            offset_tracker: None,
+            read_started: false,
         };
     }
 
@@ -235,11 +427,97 @@ impl <R: std::io::Read> I18nReader<R> {
             buffer: detector.buffer,
             read_buffer: detector.read_buffer,
             write_buffer: detector.write_buffer,
+            guessed_len: detector.guessed_len,
             transcoder,
             had_replacement_or_unmappable: detector.had_replacement_or_unmappable,
             transcode_done: detector.transcode_done,
             eof: detector.eof,
             no_transcoding_needed: detector.no_transcoding_needed,
+            had_bom: detector.had_bom,
+            bytes_guessed: detector.bytes_guessed,
+            detection: detector.detection,
+            eol_converter: None,
+            bom: None,
+            bom_head: vec![],
+            output_buffer: vec![],
+            output_done: false,
+            fill_buffer: vec![],
+            fill_pos: 0,
+            offset_tracker: None,
+            read_started: false,
+        };
+    }
+
+    /// Sets how to convert line endings (CRLF, CR and LF) in the output.
+    /// The conversion works on the code units of the destination encoding,
+    /// so it works for UTF-16 and UTF-32 as well, where line endings are 2 or 4 bytes wide.
+    /// A CRLF split across reads is treated as a single line ending.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Read;
+    /// use encoding_rs;
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(Some(encoding_rs::SHIFT_JIS), encoding_rs::UTF_16LE);
+    /// let src = b"\x83\x6E\r\n\x83\x8D\r\n"; // ハ and ロ in SHIFT_JIS with CRLF
+    /// let mut reader = transcoding_rs::I18nReader::new(src.as_ref(), transcoder)
+    ///     .eol(transcoding_rs::Eol::Lf);
+    /// let buf = &mut Vec::new();
+    /// reader.read_to_end(buf).unwrap();
+    ///
+    /// assert_eq!(b"\xCF\x30\n\x00\xED\x30\n\x00", &buf[..]); // ハ and ロ in UTF-16LE with LF
+    /// ```
+    pub fn eol(mut self: Self, eol: Eol) -> Self {
+        self.eol_converter = match eol {
+            Eol::Keep => None,
+            _ => Some(EolConverter::new(eol, self.transcoder.dst_encoding())),
+        };
+        return self;
+    }
+
+    /// Sets how to handle a BOM at the head of the output.
+    /// This takes effect only if the destination encoding is Unicode.
+    /// If not set, a BOM in the input is removed when the input is transcoded, and kept when no transcoding is needed.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Read;
+    /// use encoding_rs;
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(Some(encoding_rs::UTF_16LE), encoding_rs::UTF_8);
+    /// let src = b"\xFF\xFEa\x00"; // a in UTF-16LE with a BOM
+    /// let mut reader = transcoding_rs::I18nReader::new(src.as_ref(), transcoder)
+    ///     .bom(transcoding_rs::Bom::Keep);
+    /// let buf = &mut Vec::new();
+    /// reader.read_to_end(buf).unwrap();
+    ///
+    /// assert_eq!(b"\xEF\xBB\xBFa", &buf[..]); // a in UTF-8 with a BOM
+    /// ```
+    pub fn bom(mut self: Self, bom: Bom) -> Self {
+        self.bom = Some(bom);
+        return self;
+    }
+
+    // Adds or removes a BOM at the head of the output, returning None until enough bytes are given to tell whether it starts with a BOM.
+    fn apply_bom(self: &mut Self, policy: Bom, output: Vec<u8>) -> Option<Vec<u8>> {
+        let bom = match self.transcoder.dst_encoding().bom() {
+            Some(bom) => bom,
+            None => return Some(output),
+        };
+        self.bom_head.extend_from_slice(&output);
+        if self.bom_head.len() < bom.len() && !self.output_done {
+            return None;
+        }
+        let head = std::mem::take(&mut self.bom_head);
+        let adds_bom = match policy {
+            Bom::Add => true,
+            Bom::Remove => false,
+            Bom::Keep => self.had_bom(),
+        };
+        return match (adds_bom, head.starts_with(bom)) {
+            (true, false) => Some([bom, &head].concat()),
+            (false, true) => Some(head[bom.len()..].into()),
+            _ => Some(head),
         };
     }
 
@@ -251,6 +529,8 @@ impl <R: std::io::Read> I18nReader<R> {
     }
 
     fn run_transcode(self: &mut Self, buffer: &mut[u8]) -> usize {
+        self.read_buffer.drain(..self.guessed_len); // no longer needed by char_indices()
+        self.guessed_len = 0;
         let src = &mut self.read_buffer;
 
         if src.len() == 0 && !self.eof { // encoding_rs unable to handle unnecessary calls well, so let's skip them
@@ -260,6 +540,9 @@ impl <R: std::io::Read> I18nReader<R> {
         if buffer.len() > 16 { // buffer has enough bytes for encoding_rs to write output
             let rslt = self.transcoder.transcode(src, buffer, self.eof);
             let (coder_result, num_read, num_written, has_replacement) = rslt;
+            if let Some(tracker) = self.offset_tracker.as_mut() {
+                tracker.feed(&src[..num_read], self.eof && coder_result == enc::CoderResult::InputEmpty);
+            }
             self.read_buffer = src[num_read..].into();
             self.had_replacement_or_unmappable = self.had_replacement_or_unmappable || has_replacement;
             self.transcode_done = (coder_result == enc::CoderResult::InputEmpty) && self.eof;
@@ -270,6 +553,9 @@ impl <R: std::io::Read> I18nReader<R> {
             let write_buffer = &mut [0u8; 8*1024];
             let rslt = self.transcoder.transcode(src, write_buffer, self.eof);
             let (coder_result, num_read, num_written, has_replacement) = rslt;
+            if let Some(tracker) = self.offset_tracker.as_mut() {
+                tracker.feed(&src[..num_read], self.eof && coder_result == enc::CoderResult::InputEmpty);
+            }
             self.read_buffer = src[num_read..].into();
             self.write_buffer = write_buffer[..num_written].into();
             self.had_replacement_or_unmappable = self.had_replacement_or_unmappable || has_replacement;
@@ -287,17 +573,85 @@ impl <R: std::io::Read> I18nReader<R> {
     pub fn had_replacement_or_unmappable(self: &Self) ->bool {
         return self.had_replacement_or_unmappable;
     }
-}
 
-impl <R: std::io::Read> std::io::Read for I18nReader<R> {
+    /// Whether the input starts with a BOM.
+    /// If the reader is not created by `I18nReaderEncodingDetector`, this is known after the first read.
+    pub fn had_bom(self: &Self) -> bool {
+        return self.had_bom.unwrap_or(false);
+    }
 
-    /// Reads the input using the specified source encoding and transcode to
-    /// UTF-8 or the specified destination encoding.
-    fn read(self: &mut Self, buffer: &mut [u8]) -> std::io::Result<usize> {
+    /// The number of bytes examined to guess the encoding.
+    /// This is 0 if the reader is not created by `I18nReaderEncodingDetector`.
+    pub fn bytes_guessed(self: &Self) -> usize {
+        return self.bytes_guessed;
+    }
 
-        if buffer.len() == 0 {
-            return Ok(0);
+    /// The details of the encoding detection including the candidate encodings.
+    /// This is None if the reader is not created by `I18nReaderEncodingDetector`.
+    pub fn detection(self: &Self) -> Option<&Detection> {
+        return self.detection.as_ref();
+    }
+
+    /// Returns an iterator over the decoded characters with the byte offsets in the input where they start.
+    /// Since the offsets are of the input, line endings and BOMs are not converted even if `eol()` or `bom()` is set,
+    /// and a BOM is not returned.
+    /// The destination encoding needs to be UTF-8, the source encoding needs to be known,
+    /// and this needs to be called before reading from the reader.
+    /// Otherwise the iterator returns an error of the `std::io::ErrorKind::InvalidInput` kind.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(Some(encoding_rs::SHIFT_JIS), encoding_rs::UTF_8);
+    /// let src = b"a\x83\x6Eb"; // aハb in SHIFT_JIS
+    /// let reader = transcoding_rs::I18nReader::new(src.as_ref(), transcoder);
+    /// let chars: Vec<(usize, char)> = reader.char_indices().map(|c| c.unwrap()).collect();
+    ///
+    /// assert_eq!(vec![(0, 'a'), (1, 'ハ'), (3, 'b')], chars);
+    /// ```
+    pub fn char_indices(mut self: Self) -> CharIndices<R> {
+        if self.read_started {
+            let err = std::io::Error::new(std::io::ErrorKind::InvalidInput, "the reader has already been read from");
+            return CharIndices::new(self, Some(err));
         }
+        if !self.no_transcoding_needed {
+            let src_encoding = match self.transcoder.src_encoding() {
+                Some(src_encoding) => src_encoding,
+                None => {
+                    let err = std::io::Error::new(std::io::ErrorKind::InvalidInput, "the source encoding is unknown");
+                    return CharIndices::new(self, Some(err));
+                },
+            };
+            // the input consumed by the guess is decoded again to know the offsets of the characters in it.
+            let mut tracker = OffsetTracker::new(src_encoding);
+            tracker.feed(&self.read_buffer[..self.guessed_len], self.transcode_done);
+            self.offset_tracker = Some(tracker);
+        }
+        return CharIndices::new(self, None);
+    }
+
+    /// Returns an iterator over the decoded lines with the byte offsets in the input where they start.
+    /// The lines don't include the line endings, which are any of CRLF, CR and LF.
+    /// See `char_indices()` for the requirements.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(Some(encoding_rs::SHIFT_JIS), encoding_rs::UTF_8);
+    /// let src = b"\x83\x6E\r\n\x83\x8D"; // ハ and ロ in SHIFT_JIS with CRLF
+    /// let reader = transcoding_rs::I18nReader::new(src.as_ref(), transcoder);
+    /// let lines: Vec<(usize, String)> = reader.line_indices().map(|l| l.unwrap()).collect();
+    ///
+    /// assert_eq!(vec![(0, "ハ".to_string()), (4, "ロ".to_string())], lines);
+    /// ```
+    pub fn line_indices(self: Self) -> LineIndices<R> {
+        return LineIndices::new(self.char_indices());
+    }
+
+    pub(crate) fn read_transcoded(self: &mut Self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        self.read_started = true;
 
         if self.write_buffer.len() > 0 {
             let num_written = self.copy_from_write_buffer_to(buffer);
@@ -309,22 +663,109 @@ impl <R: std::io::Read> std::io::Read for I18nReader<R> {
             return Ok(n);
         }
 
+        if let Some(err) = self.transcoder.error() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err.clone()));
+        }
+
         if self.transcode_done {
             return Ok(0);
         }
 
-        if self.read_buffer.len() > 0 {
+        loop { // the decoder may consume input without writing output, so let's repeat until something is written
+            let reads_bom = self.had_bom.is_none() && !self.eof && self.read_buffer.len() < 4; // a BOM is up to 4 bytes
+            if self.read_buffer.len() == 0 || reads_bom {
+                let n = self.reader.read(&mut self.buffer)?;
+                self.read_buffer.extend_from_slice(&self.buffer[..n]);
+                self.eof = n == 0;
+                if self.had_bom.is_none() && !self.eof && self.read_buffer.len() < 4 {
+                    continue;
+                }
+                if self.had_bom.is_none() {
+                    self.had_bom = Some(Encoding::for_bom(&self.read_buffer).is_some());
+                }
+            }
             let num_written = self.run_transcode(buffer);
-            if num_written > 0 {
+            if num_written > 0 || self.transcode_done {
                 return Ok(num_written);
             }
+            if let Some(err) = self.transcoder.error() {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err.clone()));
+            }
+        }
+    }
+}
+
+impl <R: std::io::Read> std::io::Read for I18nReader<R> {
+
+    /// Reads the input using the specified source encoding and transcode to
+    /// UTF-8 or the specified destination encoding.
+    fn read(self: &mut Self, buffer: &mut [u8]) -> std::io::Result<usize> {
+
+        if buffer.len() == 0 {
+            return Ok(0);
+        }
+
+        if self.fill_pos < self.fill_buffer.len() { // left by fill_buf()
+            let min = std::cmp::min(buffer.len(), self.fill_buffer.len() - self.fill_pos);
+            buffer[..min].copy_from_slice(&self.fill_buffer[self.fill_pos..self.fill_pos+min]);
+            self.fill_pos += min;
+            return Ok(min);
         }
 
-        let n = self.reader.read(&mut self.buffer)?;
-        self.read_buffer = self.buffer[..n].into();
-        self.eof = n == 0;
-        let num_written = self.run_transcode(buffer);
-        return Ok(num_written);
+        if self.eol_converter.is_none() && self.bom.is_none() && self.output_buffer.is_empty() {
+            return self.read_transcoded(buffer);
+        }
+
+        loop { // converting line endings and BOMs may change the length, so the output is buffered
+            if self.output_buffer.len() > 0 {
+                let min = std::cmp::min(buffer.len(), self.output_buffer.len());
+                buffer[..min].copy_from_slice(&self.output_buffer[..min]);
+                self.output_buffer.drain(..min);
+                return Ok(min);
+            }
+            if self.output_done {
+                return Ok(0);
+            }
+            let transcoded = &mut [0u8; 8*1024];
+            let n = self.read_transcoded(transcoded)?;
+            self.output_done = n == 0;
+            let mut output = match self.eol_converter.as_mut() {
+                Some(converter) => converter.convert(&transcoded[..n], self.output_done),
+                None => transcoded[..n].into(),
+            };
+            if let Some(policy) = self.bom {
+                match self.apply_bom(policy, output) {
+                    Some(applied) => {
+                        output = applied;
+                        self.bom = None; // only the head of the output matters
+                    },
+                    None => continue,
+                }
+            }
+            self.output_buffer = output;
+        }
+    }
+}
+
+impl <R: std::io::Read> std::io::BufRead for I18nReader<R> {
+
+    /// Returns the rest of the output in the internal buffer, filling it by `read()` if it's empty.
+    /// The output is copied to the buffer just as `BufReader` would, but a `BufReader` isn't needed on top of this.
+    /// If the destination encoding is UTF-8, `lines()` returns the decoded lines.
+    fn fill_buf(self: &mut Self) -> std::io::Result<&[u8]> {
+        if self.fill_pos >= self.fill_buffer.len() {
+            let mut fill_buffer = std::mem::take(&mut self.fill_buffer);
+            fill_buffer.resize(8*1024, 0);
+            let n = std::io::Read::read(self, &mut fill_buffer)?;
+            fill_buffer.truncate(n);
+            self.fill_buffer = fill_buffer;
+            self.fill_pos = 0;
+        }
+        return Ok(&self.fill_buffer[self.fill_pos..]);
+    }
+
+    fn consume(self: &mut Self, amt: usize) {
+        self.fill_pos = std::cmp::min(self.fill_pos + amt, self.fill_buffer.len());
     }
 }
 
@@ -378,6 +819,50 @@ mod tests {
         }
     }
 
+    // Reads a byte at a time, like a slow pipe.
+    struct OneByteReader<'a>(&'a [u8]);
+
+    impl<'a> Read for OneByteReader<'a> {
+        fn read(self: &mut Self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.len() == 0 || buf.len() == 0 {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            return Ok(1);
+        }
+    }
+
+    #[test]
+    fn reader_short_reads() {
+        let src = b"\x83\x6E\x83\x8D\x81\x5B"; // ハロー in SHIFT_JIS
+        let f = I18nReaderEncodingDetector::new().allowed_encodings(&[enc::SHIFT_JIS]);
+        let r = f.guess_utf8(OneByteReader(src)).unwrap();
+        if let GuessResult::Success(mut reader, _) = r {
+            let mut buff = Vec::new();
+            reader.read_to_end(&mut buff).unwrap();
+            assert_eq!("ハロー".as_bytes(), &buff[..]);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn reader_bom_short_reads() {
+        let src = b"\xEF\xBB\xBFab"; // ab in UTF-8 with a BOM
+        let r = I18nReaderEncodingDetector::new().guess(OneByteReader(src), enc::UTF_16LE).unwrap();
+        if let GuessResult::Success(reader, _) = r {
+            assert!(reader.had_bom());
+        } else {
+            panic!();
+        }
+        let mut reader = I18nReader::new(OneByteReader(src), Transcoder::new(Some(enc::UTF_8), enc::UTF_16LE));
+        let mut buff = Vec::new();
+        reader.read_to_end(&mut buff).unwrap();
+        assert!(reader.had_bom());
+        assert_eq!(b"a\x00b\x00", &buff[..]);
+    }
+
     #[test]
     fn reader_fail() {
         let src = b"\x00\x00\x00\x00\x00\x00";
@@ -403,6 +888,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn reader_strict() {
+        let src = b"\x83\x6E\x83\x8D\x81\x5B\x83\x8F\x81\x5B\x83\x8B\x83\x68ab\x84"; // ends with a truncated sequence
+        let f = I18nReaderEncodingDetector::new().bytes_to_guess(16).strict(true);
+        let r = f.guess_utf8(src.as_ref()).unwrap();
+        if let GuessResult::Success(mut reader, _) = r {
+            let mut buff = Vec::new();
+            let err = reader.read_to_end(&mut buff).unwrap_err();
+            assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+            let transcode_err = err.get_ref().unwrap().downcast_ref::<crate::TranscodeError>().unwrap();
+            assert_eq!(&crate::TranscodeError::Malformed { offset: 16 }, transcode_err);
+            assert_eq!("ハローワールドab".as_bytes(), &buff[..]);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn reader_strict_small_reads() {
+        let src = "ハローワールド".repeat(4);
+        let (sjis, _, _) = enc::SHIFT_JIS.encode(&src);
+        let t = Transcoder::new(Some(enc::SHIFT_JIS), enc::UTF_8).strict(true);
+        let mut reader = I18nReader::new(&sjis[..], t);
+        let mut output = Vec::new();
+        let buff = &mut [0u8; 20]; // small enough for the strict path to write byte by byte
+        loop {
+            let n = reader.read(buff).unwrap();
+            if n == 0 {
+                break;
+            }
+            output.extend_from_slice(&buff[..n]);
+        }
+        assert_eq!(src.as_bytes(), &output[..]);
+    }
+
     #[test]
     fn reader_no_guess() {
         let src = b"\x83\x6E\x83\x8D\x81\x5B\x83\x8F\x81\x5B\x83\x8B\x83\x68";
@@ -412,5 +932,109 @@ mod tests {
         let n = reader.read(&mut buff).unwrap();
         assert_eq!("ハローワールド".as_bytes(), &buff[..n])
     }
+
+    #[test]
+    fn reader_eol() {
+        // the CRLF is split across the guessed bytes and the rest
+        let src = b"\x83\x6E\x83\x8D\x81\x5B\r\n\x83\x8F\x81\x5B\x83\x8B\x83\x68\r";
+        let f = I18nReaderEncodingDetector::new().bytes_to_guess(8).buffer_size(16).eol(crate::Eol::Lf);
+        let r = f.guess(src.as_ref(), enc::UTF_16BE).unwrap();
+        if let GuessResult::Success(mut reader, _) = r {
+            let mut buff = Vec::new();
+            reader.read_to_end(&mut buff).unwrap();
+            let expected: Vec<u8> = "ハロー\nワールド\n".encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+            assert_eq!(expected, buff);
+        } else {
+            panic!();
+        }
+        let src = b"a\r\nb\nc\r";
+        let f = I18nReaderEncodingDetector::new().eol(crate::Eol::Crlf); // no transcoding is needed
+        if let GuessResult::Success(mut reader, _) = f.guess_utf8(src.as_ref()).unwrap() {
+            let mut buff = Vec::new();
+            reader.read_to_end(&mut buff).unwrap();
+            assert_eq!(b"a\r\nb\r\nc\r\n".to_vec(), buff);
+        } else {
+            panic!();
+        }
+    }
+
+    macro_rules! bom_test {
+        ($name:ident, $bom:expr, $dst:expr, $src:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let f = I18nReaderEncodingDetector::new().add_bom_utf16(true).bom($bom);
+                if let GuessResult::Success(mut reader, _) = f.guess($src.as_ref(), $dst).unwrap() {
+                    let mut buff = Vec::new();
+                    reader.read_to_end(&mut buff).unwrap();
+                    assert_eq!($expected.to_vec(), buff);
+                } else {
+                    panic!();
+                }
+            }
+        };
+    }
+
+    bom_test!(bom_add_utf8          , crate::Bom::Add   , enc::UTF_8   , b"abc"                       , b"\xEF\xBB\xBFabc");
+    bom_test!(bom_add_utf8_bom      , crate::Bom::Add   , enc::UTF_8   , b"\xEF\xBB\xBFabc"           , b"\xEF\xBB\xBFabc");
+    bom_test!(bom_remove_utf8_bom   , crate::Bom::Remove, enc::UTF_8   , b"\xEF\xBB\xBFabc"           , b"abc"); // no transcoding is needed
+    bom_test!(bom_remove_utf16le    , crate::Bom::Remove, enc::UTF_16LE, b"abc"                       , b"a\x00b\x00c\x00");
+    bom_test!(bom_keep_utf16_utf8   , crate::Bom::Keep  , enc::UTF_8   , b"\xFF\xFEa\x00b\x00c\x00"   , b"\xEF\xBB\xBFabc");
+    bom_test!(bom_keep_utf8_utf16be , crate::Bom::Keep  , enc::UTF_16BE, b"abc"                       , b"\x00a\x00b\x00c");
+    bom_test!(bom_add_utf32be       , crate::Bom::Add   , crate::Encoding::Utf32Be, b"a"              , b"\x00\x00\xFE\xFF\x00\x00\x00a");
+    bom_test!(bom_add_sjis          , crate::Bom::Add   , enc::SHIFT_JIS, b"abc"                      , b"abc"); // not Unicode
+
+    #[test]
+    fn reader_bom_large() {
+        // the output with a BOM added is longer than the internal buffer
+        let src = "ハ".repeat(5000);
+        let t = Transcoder::new(Some(enc::UTF_8), enc::UTF_16LE);
+        let mut reader = I18nReader::new(src.as_bytes(), t).bom(crate::Bom::Add);
+        let mut buff = Vec::new();
+        let chunk = &mut [0u8; 8*1024];
+        loop {
+            let n = reader.read(chunk).unwrap();
+            if n == 0 {
+                break;
+            }
+            buff.extend_from_slice(&chunk[..n]);
+        }
+        let expected: Vec<u8> = format!("\u{FEFF}{}", src).encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        assert_eq!(expected, buff);
+    }
+
+    #[test]
+    fn reader_report() {
+        let src = b"\xFF\xFEa\x00b\x00c\x00";
+        let f = I18nReaderEncodingDetector::new();
+        let r = f.guess_utf8(src.as_ref()).unwrap();
+        if let GuessResult::Success(reader, _) = r {
+            assert!(reader.had_bom());
+            assert_eq!(8, reader.bytes_guessed());
+        } else {
+            panic!();
+        }
+        let t = Transcoder::new(Some(enc::UTF_8), enc::UTF_8);
+        let mut reader = I18nReader::new(b"\xEF\xBB\xBFabc".as_ref(), t);
+        assert!(!reader.had_bom());
+        let mut buff = Vec::new();
+        reader.read_to_end(&mut buff).unwrap();
+        assert!(reader.had_bom());
+        assert_eq!(0, reader.bytes_guessed());
+    }
+
+    #[test]
+    fn reader_buf_read() {
+        use std::io::BufRead;
+        let src = b"\x83\x6E\r\n\x83\x8D\r\nab"; // ハ, ロ and ab in SHIFT_JIS with CRLF
+        let t = Transcoder::new(Some(enc::SHIFT_JIS), enc::UTF_8);
+        let mut reader = I18nReader::new(src.as_ref(), t).eol(Eol::Lf);
+        assert_eq!("ハ\nロ\nab".as_bytes(), reader.fill_buf().unwrap());
+        reader.consume(1);
+        let mut buff = [0u8; 3];
+        reader.read_exact(&mut buff).unwrap(); // reads the rest of the buffer filled
+        assert_eq!(b"\x83\x8F\n", &buff); // the rest of ハ and LF
+        let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
+        assert_eq!(vec!["ロ", "ab"], lines);
+    }
 }
 
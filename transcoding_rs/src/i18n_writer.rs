@@ -0,0 +1,399 @@
+//! Transcoding on the write side.
+
+use encoding_rs as enc;
+use std::io::Write;
+use crate::Transcoder;
+use crate::Encoding;
+use crate::Bom;
+
+/// Writer that transcodes the input written in the source encoding to the destination encoding.
+/// A character split across writes is kept until the rest of it is written.
+///
+/// Since the end of the input can't be known from writes, the writer needs to be finished by `finish()`,
+/// which writes the characters kept pending and reports whether replacement characters or unmappable characters are seen.
+/// `flush()` writes the output transcoded so far but doesn't finish the writer, since more input may follow.
+/// The writer is finished on drop as well, in which case errors are ignored.
+///
+/// As `Write` requires, a write that has consumed any input returns the number of bytes consumed even if an error occurs
+/// after that, and the error is returned by the next call. So `write_all()` fails at the error without repeating the output.
+///
+/// # Example
+/// ```
+/// use std::io::Write;
+/// use encoding_rs;
+///
+/// let transcoder = transcoding_rs::Transcoder::new(Some(encoding_rs::UTF_8), encoding_rs::SHIFT_JIS);
+/// let mut writer = transcoding_rs::I18nWriter::new(Vec::new(), transcoder);
+/// let src = "ハロー".as_bytes();
+/// writer.write_all(&src[..4]).unwrap(); // the second character is split
+/// writer.write_all(&src[4..]).unwrap();
+/// let had_unmappable = writer.finish().unwrap();
+///
+/// assert!(!had_unmappable);
+/// assert_eq!(b"\x83\x6E\x83\x8D\x81\x5B", &writer.get_ref()[..]); // ハロー in SHIFT_JIS
+/// ```
+pub struct I18nWriter<W: Write> {
+    writer: Option<W>,
+    buffer: Vec<u8>,
+    transcoder: Transcoder,
+    had_replacement_or_unmappable: bool,
+    finished: bool,
+    input_head: Vec<u8>,
+    bom: Option<Bom>,
+    bom_head: Vec<u8>,
+    pending_output: Vec<u8>,
+}
+
+impl <W: Write> I18nWriter<W> {
+
+    /// Creates a new `I18nWriter`.
+    /// `Transcoder` needs to have the source encoding.
+    /// A BOM in the input is removed unless `bom()` is set.
+    pub fn new(writer: W, transcoder: Transcoder) -> Self {
+        return Self {
+            writer: Some(writer),
+            buffer: vec![0u8; 8*1024],
+            transcoder,
+            had_replacement_or_unmappable: false,
+            finished: false,
+            input_head: vec![],
+            bom: None,
+            bom_head: vec![],
+            pending_output: vec![],
+        };
+    }
+
+    /// Sets how to handle a BOM at the head of the output.
+    /// This takes effect only if the destination encoding is Unicode.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use encoding_rs;
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(Some(encoding_rs::UTF_8), encoding_rs::UTF_16LE);
+    /// let mut writer = transcoding_rs::I18nWriter::new(Vec::new(), transcoder)
+    ///     .bom(transcoding_rs::Bom::Add);
+    /// writer.write_all(b"a").unwrap();
+    /// writer.finish().unwrap();
+    ///
+    /// assert_eq!(b"\xFF\xFEa\x00", &writer.get_ref()[..]); // a in UTF-16LE with a BOM
+    /// ```
+    pub fn bom(mut self: Self, bom: Bom) -> Self {
+        self.bom = Some(bom);
+        return self;
+    }
+
+    /// Whether replacement characters or unmappable characters are seen so far.
+    pub fn had_replacement_or_unmappable(self: &Self) -> bool {
+        return self.had_replacement_or_unmappable;
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(self: &Self) -> &W {
+        return self.writer.as_ref().unwrap();
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    /// Writing to it directly may corrupt the output.
+    pub fn get_mut(self: &mut Self) -> &mut W {
+        return self.writer.as_mut().unwrap();
+    }
+
+    /// Finishes the writer and returns the underlying writer.
+    pub fn into_inner(mut self: Self) -> std::io::Result<W> {
+        self.finish()?;
+        return Ok(self.writer.take().unwrap());
+    }
+
+    /// Transcodes the input kept pending as the end of the input, and flushes the underlying writer.
+    /// Returns whether replacement characters or unmappable characters are seen.
+    /// In the strict mode or with `UnmappablePolicy::Error`, the first one of them is reported as an error instead,
+    /// which is of the `std::io::ErrorKind::InvalidData` kind and wraps `TranscodeError`.
+    /// Writing after this is an error. Calling this again does nothing but flushing.
+    pub fn finish(self: &mut Self) -> std::io::Result<bool> {
+        if !self.finished {
+            self.transcode(&[], true)?;
+            self.finished = true;
+        }
+        self.write_pending_output()?;
+        self.get_mut().flush()?;
+        return Ok(self.had_replacement_or_unmappable);
+    }
+
+    // Transcodes the input and writes the output, returning the number of bytes of the input consumed.
+    // Errors after any input is consumed are left to the next call.
+    fn transcode(self: &mut Self, src: &[u8], last: bool) -> std::io::Result<usize> {
+        self.write_pending_output()?;
+        if let Some(err) = self.transcoder.error() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err.clone()));
+        }
+        if self.input_head.len() < 4 {
+            let n = std::cmp::min(4 - self.input_head.len(), src.len());
+            self.input_head.extend_from_slice(&src[..n]);
+        }
+        let mut num_read_total = 0;
+        loop { // the output may not fit in the buffer, so let's repeat until the input is all read
+            let rslt = self.transcoder.transcode(&src[num_read_total..], &mut self.buffer, last);
+            let (coder_result, num_read, num_written, has_replacement) = rslt;
+            num_read_total += num_read;
+            self.had_replacement_or_unmappable = self.had_replacement_or_unmappable || has_replacement;
+            let output = self.buffer[..num_written].to_vec();
+            self.queue_output(output, last && coder_result == enc::CoderResult::InputEmpty);
+            let written = self.write_pending_output(); // kept pending on errors
+            if num_read_total > 0 && (written.is_err() || self.transcoder.error().is_some()) {
+                return Ok(num_read_total);
+            }
+            written?;
+            if let Some(err) = self.transcoder.error() {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err.clone()));
+            }
+            if coder_result == enc::CoderResult::InputEmpty {
+                return Ok(num_read_total);
+            }
+        }
+    }
+
+    fn queue_output(self: &mut Self, output: Vec<u8>, done: bool) {
+        let output = match self.bom {
+            Some(policy) => match self.apply_bom(policy, output, done) {
+                Some(applied) => {
+                    self.bom = None; // only the head of the output matters
+                    applied
+                },
+                None => return,
+            },
+            None => output,
+        };
+        self.pending_output.extend_from_slice(&output);
+    }
+
+    // Writes the output not written yet, which is kept as far as it's not written on errors.
+    fn write_pending_output(self: &mut Self) -> std::io::Result<()> {
+        while !self.pending_output.is_empty() {
+            let writer = self.writer.as_mut().unwrap();
+            match writer.write(&self.pending_output) {
+                Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write the output")),
+                Ok(n) => { self.pending_output.drain(..n); },
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(err) => return Err(err),
+            }
+        }
+        return Ok(());
+    }
+
+    // Adds or removes a BOM at the head of the output, returning None until enough bytes are given to tell whether it starts with a BOM.
+    fn apply_bom(self: &mut Self, policy: Bom, output: Vec<u8>, done: bool) -> Option<Vec<u8>> {
+        let bom = match self.transcoder.dst_encoding().bom() {
+            Some(bom) => bom,
+            None => return Some(output),
+        };
+        self.bom_head.extend_from_slice(&output);
+        if self.bom_head.len() < bom.len() && !done {
+            return None;
+        }
+        let head = std::mem::take(&mut self.bom_head);
+        let adds_bom = match policy {
+            Bom::Add => true,
+            Bom::Remove => false,
+            Bom::Keep => Encoding::for_bom(&self.input_head).is_some(),
+        };
+        return match (adds_bom, head.starts_with(bom)) {
+            (true, false) => Some([bom, &head].concat()),
+            (false, true) => Some(head[bom.len()..].into()),
+            _ => Some(head),
+        };
+    }
+}
+
+impl <W: Write> Write for I18nWriter<W> {
+
+    /// Transcodes the input and writes the output to the underlying writer.
+    /// A character split at the end of the input is kept until the next write.
+    fn write(self: &mut Self, buffer: &[u8]) -> std::io::Result<usize> {
+        if self.finished {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "I18nWriter is already finished"));
+        }
+        if buffer.len() == 0 {
+            return Ok(0);
+        }
+        return self.transcode(buffer, false);
+    }
+
+    /// Writes the output transcoded so far, and flushes the underlying writer.
+    /// A character split at the end of the input is still kept, so use `finish()` at the end of the input.
+    /// With `bom()`, the head of the output is kept as well until it tells whether the output starts with a BOM,
+    /// which is up to 3 bytes, or until `finish()`.
+    fn flush(self: &mut Self) -> std::io::Result<()> {
+        self.write_pending_output()?;
+        return self.get_mut().flush();
+    }
+}
+
+impl <W: Write> Drop for I18nWriter<W> {
+    fn drop(self: &mut Self) {
+        if self.writer.is_some() && !self.finished && !std::thread::panicking() {
+            let _ = self.finish(); // errors are ignored like BufWriter
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::path;
+    use super::*;
+    use crate::TranscodeError;
+    use crate::UnmappablePolicy;
+
+    macro_rules! test_writer {
+        ($name:ident, $input_file:expr, $src_enc:expr, $expected_file:expr, $dst_enc:expr) => {
+            #[test]
+            fn $name() {
+                let test_data = path::Path::new("../test_data");
+                let input = &mut Vec::new();
+                std::fs::File::open(test_data.join($input_file)).unwrap().read_to_end(input).unwrap();
+                let src_enc = enc::Encoding::for_label($src_enc.as_bytes()).unwrap();
+                let dst_enc = enc::Encoding::for_label($dst_enc.as_bytes()).unwrap();
+                let mut writer = I18nWriter::new(Vec::new(), Transcoder::new(Some(src_enc), dst_enc));
+                for chunk in input.chunks(7) { // split characters across writes
+                    writer.write_all(chunk).unwrap();
+                }
+                let output = writer.into_inner().unwrap();
+                let expected = &mut Vec::new();
+                std::fs::File::open(test_data.join($expected_file)).unwrap().read_to_end(expected).unwrap();
+                assert!(expected == &output);
+            }
+        };
+    }
+
+    test_writer!(writer_utf8_sjis        , "utf8_ja.txt"     , "utf8"  , "sjis_ja.txt"         , "sjis");
+    test_writer!(writer_utf8_euckr       , "utf8_ko.txt"     , "utf8"  , "euc-kr_ko.txt"       , "euc-kr");
+    test_writer!(writer_sjis_utf8        , "sjis_ja.txt"     , "sjis"  , "utf8_ja.txt"         , "utf8");
+
+    #[test]
+    fn writer_unmappable() {
+        let transcoder = Transcoder::new(Some(enc::UTF_8), enc::SHIFT_JIS).unmappable(UnmappablePolicy::Substitute('?'));
+        let mut writer = I18nWriter::new(Vec::new(), transcoder);
+        writer.write_all("caf".as_bytes()).unwrap();
+        assert!(!writer.had_replacement_or_unmappable());
+        writer.write_all("é".as_bytes()).unwrap();
+        assert!(writer.finish().unwrap());
+        assert_eq!(b"caf?", &writer.get_ref()[..]);
+    }
+
+    #[test]
+    fn writer_unmappable_error() {
+        let transcoder = Transcoder::new(Some(enc::UTF_8), enc::SHIFT_JIS).unmappable(UnmappablePolicy::Error);
+        let mut writer = I18nWriter::new(Vec::new(), transcoder);
+        assert_eq!(5, writer.write("abcé".as_bytes()).unwrap()); // the error is returned by the next call
+        let err = writer.write_all("d".as_bytes()).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+        let transcode_err = err.get_ref().unwrap().downcast_ref::<TranscodeError>().unwrap();
+        assert_eq!(&TranscodeError::Unmappable { offset: 3, character: 'é' }, transcode_err);
+        assert_eq!(b"abc", &writer.get_ref()[..]);
+    }
+
+    #[test]
+    fn writer_unmappable_error_consumed() {
+        let transcoder = Transcoder::new(Some(enc::UTF_8), enc::SHIFT_JIS).unmappable(UnmappablePolicy::Error);
+        let mut writer = I18nWriter::new(Vec::new(), transcoder);
+        let src = "abéc".as_bytes();
+        let n = writer.write(src).unwrap();
+        assert_eq!(4, n); // up to the unmappable character
+        assert!(writer.write(&src[n..]).is_err());
+        assert!(writer.finish().is_err());
+        assert_eq!(b"ab", &writer.get_ref()[..]);
+    }
+
+    // Fails every other write, like an interrupted pipe.
+    struct Flaky {
+        output: Vec<u8>,
+        fails: bool,
+    }
+
+    impl Write for Flaky {
+        fn write(self: &mut Self, buffer: &[u8]) -> std::io::Result<usize> {
+            self.fails = !self.fails;
+            if self.fails {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, "flaky"));
+            }
+            self.output.extend_from_slice(buffer);
+            return Ok(buffer.len());
+        }
+
+        fn flush(self: &mut Self) -> std::io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn writer_retry() {
+        let transcoder = Transcoder::new(Some(enc::UTF_8), enc::SHIFT_JIS);
+        let mut writer = I18nWriter::new(Flaky { output: vec![], fails: false }, transcoder);
+        for chunk in "ハロー".as_bytes().chunks(3) {
+            let mut written = 0;
+            while written < chunk.len() { // retry on errors like a caller of write() would
+                if let Ok(n) = writer.write(&chunk[written..]) {
+                    written += n;
+                }
+            }
+        }
+        while writer.finish().is_err() {}
+        assert_eq!(b"\x83\x6E\x83\x8D\x81\x5B", &writer.get_ref().output[..]); // ハロー in SHIFT_JIS without duplicates
+    }
+
+    #[test]
+    fn writer_strict_truncated() {
+        let transcoder = Transcoder::new(Some(enc::UTF_8), enc::SHIFT_JIS).strict(true);
+        let mut writer = I18nWriter::new(Vec::new(), transcoder);
+        writer.write_all(&"ハ".as_bytes()[..2]).unwrap(); // kept pending until finish
+        let err = writer.finish().unwrap_err();
+        let transcode_err = err.get_ref().unwrap().downcast_ref::<TranscodeError>().unwrap();
+        assert_eq!(&TranscodeError::Malformed { offset: 0 }, transcode_err);
+    }
+
+    #[test]
+    fn writer_flush_keeps_partial() {
+        let mut writer = I18nWriter::new(Vec::new(), Transcoder::new(Some(enc::UTF_8), enc::EUC_JP));
+        writer.write_all(&"aハ".as_bytes()[..2]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(b"a", &writer.get_ref()[..]);
+        writer.write_all(&"aハ".as_bytes()[2..]).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(b"a\xA5\xCF", &writer.get_ref()[..]);
+        assert!(writer.write(b"a").is_err());
+    }
+
+    #[test]
+    fn writer_drop() {
+        let output = &mut Vec::new();
+        {
+            let transcoder = Transcoder::new(Some(enc::UTF_8), enc::UTF_16BE);
+            let mut writer = I18nWriter::new(&mut *output, transcoder).bom(Bom::Add);
+            writer.write_all(b"a").unwrap();
+        }
+        assert_eq!(b"\xFE\xFF\x00a", &output[..]);
+    }
+
+    macro_rules! bom_test {
+        ($name:ident, $src:expr, $src_enc:expr, $dst_enc:expr, $bom:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let transcoder = Transcoder::new(Some($src_enc), $dst_enc);
+                let mut writer = I18nWriter::new(Vec::new(), transcoder).bom($bom);
+                for byte in $src.iter() {
+                    writer.write_all(&[*byte]).unwrap();
+                }
+                assert_eq!($expected.to_vec(), writer.into_inner().unwrap());
+            }
+        };
+    }
+
+    bom_test!(writer_bom_keep_utf16le   , b"\xFF\xFEa\x00"    , enc::UTF_16LE , enc::UTF_8    , Bom::Keep   , b"\xEF\xBB\xBFa");
+    bom_test!(writer_bom_keep_none      , b"a"                , enc::UTF_8    , enc::UTF_16LE , Bom::Keep   , b"a\x00");
+    bom_test!(writer_bom_add            , b"\xEF\xBB\xBFa"    , enc::UTF_8    , enc::UTF_8    , Bom::Add    , b"\xEF\xBB\xBFa");
+    bom_test!(writer_bom_remove         , b"\xEF\xBB\xBFa"    , enc::UTF_8    , enc::UTF_16BE , Bom::Remove , b"\x00a");
+    bom_test!(writer_bom_empty          , b""                 , enc::UTF_8    , enc::UTF_8    , Bom::Add    , b"\xEF\xBB\xBF");
+    bom_test!(writer_bom_non_unicode    , b"a"                , enc::UTF_8    , enc::SHIFT_JIS, Bom::Add    , b"a");
+}
@@ -5,20 +5,50 @@
 //! `chardetng` is created for encoding detection and `encoding_rs` can be used for transcoding.
 //! This library aims to transcode the easy and efficient way by combining these two crates.
 //!
-//!  Note: Supported encodings are the ones defined in [the Encoding Standard](https://encoding.spec.whatwg.org).  
+//!  Note: Supported encodings are the ones defined in [the Encoding Standard](https://encoding.spec.whatwg.org), plus UTF-32LE/BE, which this library implements by itself.  
 //!
-//!  Note: UTF-16 files are needed to have a BOM to be detected as the encoding.  
-//!        This is because [`chardetng`](https://github.com/hsivonen/chardetng), on which this library depends, does not support UTF-16 and this library only added BOM sniffing to detect UTF-16.  
+//!  Note: UTF-16 and UTF-32 files without a BOM are detected by heuristics, which need null bytes, i.e. ASCII characters such as spaces and line breaks, in the text.  
+//!        This is because [`chardetng`](https://github.com/hsivonen/chardetng), on which this library depends, does not support UTF-16 and UTF-32, and this library added BOM sniffing and the heuristics to detect them.  
+//!
+//!  Note: `AsyncI18nReader`, which reads a `tokio::io::AsyncRead`, is available with the `tokio` feature.  
 //!
 
 
 mod transcoder;
 mod constants;
 mod i18n_reader;
+mod i18n_writer;
+mod error;
+mod detection;
+mod encoding;
+mod eol;
+mod bom;
+mod binary;
+mod chars;
+#[cfg(feature = "tokio")]
+mod async_reader;
 
 pub use i18n_reader::I18nReaderEncodingDetector;
 pub use i18n_reader::GuessResult;
 pub use i18n_reader::I18nReader;
+pub use chars::CharIndices;
+pub use chars::LineIndices;
+pub use i18n_writer::I18nWriter;
 pub use transcoder::Transcoder;
+pub use transcoder::UnmappablePolicy;
 pub use constants::ENCODINGS;
+pub use error::TranscodeError;
+pub use detection::Detection;
+pub use detection::Candidate;
+pub use detection::Rejection;
+pub use detection::tld_for_language;
+pub use detection::normalize_tld;
+pub use encoding::Encoding;
+pub use eol::Eol;
+pub use bom::Bom;
+pub use binary::{binary_format, UNKNOWN_FORMAT};
+#[cfg(feature = "tokio")]
+pub use async_reader::AsyncI18nReader;
+#[cfg(feature = "tokio")]
+pub use async_reader::AsyncGuessResult;
 
@@ -5,15 +5,74 @@ use chardetng as cd;
 use std::str;
 
 use crate::constants;
+use crate::TranscodeError;
+use crate::detection;
+use crate::detection::{Detection, Candidate, Rejection};
+use crate::encoding::{Encoding, Decoder, Encoder};
+
+/// How to handle characters that can't be mapped to the destination encoding.
+/// This takes effect only if the destination encoding is not Unicode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmappablePolicy {
+    /// Replaces them with the HTML numeric character references (e.g. `&#12354;`).
+    Ncr,
+    /// Replaces them with the specified character.
+    /// The character is skipped if it is unmappable as well.
+    Substitute(char),
+    /// Skips them.
+    Skip,
+    /// Replaces them with the escape sequences like `\u3042`.
+    /// Characters outside the BMP are written as the surrogate pairs (e.g. `\uD83D\uDE00`).
+    Escape,
+    /// Replaces them with the closest ASCII characters (e.g. `é` to `e`).
+    /// If no ASCII characters are close enough, `?` is used.
+    Transliterate,
+    /// Stops transcoding at the first one of them, keeping the error in `Transcoder::error()`.
+    Error,
+}
+
+impl Default for UnmappablePolicy {
+    fn default() -> Self {
+        return UnmappablePolicy::Ncr;
+    }
+}
+
+impl UnmappablePolicy {
+    fn replacement(self: &Self, c: char) -> String {
+        match self {
+            UnmappablePolicy::Ncr | UnmappablePolicy::Error => format!("&#{};", c as u32),
+            UnmappablePolicy::Substitute(s) => s.to_string(),
+            UnmappablePolicy::Skip => String::new(),
+            UnmappablePolicy::Escape => {
+                let units = &mut [0u16; 2];
+                c.encode_utf16(units).iter().map(|u| format!("\\u{:04X}", u)).collect()
+            },
+            UnmappablePolicy::Transliterate => deunicode::deunicode_char(c).unwrap_or("?").into(),
+        }
+    }
+}
 
 /// Low level API for transcoding.
 pub struct Transcoder {
-    src_encoding: Option<&'static enc::Encoding>,
-    dst_encoding: &'static enc::Encoding,
-    decoder: Option<enc::Decoder>,
-    encoder: Option<enc::Encoder>,
+    src_encoding: Option<Encoding>,
+    dst_encoding: Encoding,
+    decoder: Option<Decoder>,
+    encoder: Option<Encoder>,
     decode_buffer: Vec<u8>,
     unencoded_bytes: Vec<u8>,
+    decoder_finished: bool,
+    staged: Vec<u8>,
+    flushed_by_byte: bool,
+    strict: bool,
+    unmappable: UnmappablePolicy,
+    num_read_total: usize,
+    char_offset: usize,
+    error: Option<TranscodeError>,
+    detection: Option<Detection>,
+    allowed_encodings: Vec<Encoding>,
+    tld_hint: Option<String>,
+    bomless_heuristics: bool,
+    ranks_candidates: bool,
 }
 
 impl Transcoder {
@@ -33,19 +92,48 @@ impl Transcoder {
     ///     );
     /// ```
     pub fn new(src_encoding: Option<&'static enc::Encoding>, dst_encoding: &'static enc::Encoding) -> Self {
-        let unsupported_encoding = dst_encoding == enc::UTF_16BE || dst_encoding == enc::UTF_16LE;
-        let encoder = if unsupported_encoding {
-            None
-        } else {
-            Some(dst_encoding.new_encoder())
-        };
+        return Transcoder::for_encodings(src_encoding.map(Encoding::from), Encoding::from(dst_encoding));
+    }
+
+    /// Creates a new `Transcoder` in the same way as `new()`, but with `Encoding`s,
+    /// which include UTF-32LE/BE in addition to the encodings `encoding_rs` supports.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    /// use transcoding_rs::Encoding;
+    ///
+    /// let mut transcoder = transcoding_rs::Transcoder::for_encodings(
+    ///     Some(Encoding::Utf32Le),
+    ///     Encoding::from(encoding_rs::UTF_8)
+    ///     );
+    /// let src = b"\xFF\xFE\x00\x00a\x00\x00\x00"; // a in UTF-32LE with a BOM
+    /// let buf = &mut [0u8; 128];
+    /// let (_, _, num_written, _) = transcoder.transcode(src, buf, true);
+    ///
+    /// assert_eq!(b"a", &buf[..num_written]);
+    /// ```
+    pub fn for_encodings(src_encoding: Option<Encoding>, dst_encoding: Encoding) -> Self {
         return Transcoder {
             src_encoding,
             dst_encoding,
             decoder: src_encoding.map(|s| s.new_decoder()),
-            encoder,
+            encoder: dst_encoding.new_encoder(),
             decode_buffer: vec![0u8;8*1024],
             unencoded_bytes: Vec::with_capacity(8*1024),
+            decoder_finished: false,
+            staged: Vec::new(),
+            flushed_by_byte: false,
+            strict: false,
+            unmappable: UnmappablePolicy::Ncr,
+            num_read_total: 0,
+            char_offset: 0,
+            error: None,
+            detection: None,
+            allowed_encodings: Vec::new(),
+            tld_hint: None,
+            bomless_heuristics: true,
+            ranks_candidates: false,
         };
     }
 
@@ -70,6 +158,179 @@ impl Transcoder {
         return self;
     }
 
+    /// Sets the strict mode.
+    /// In the strict mode, malformed byte sequences and unmappable characters are not replaced.
+    /// Instead, `transcode()` stops at the first one of them and the error is kept in `error()`.
+    /// Note that `guess_and_transcode()` is not affected by this mode.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let mut transcoder = transcoding_rs::Transcoder::new(
+    ///     Some(encoding_rs::SHIFT_JIS),
+    ///     encoding_rs::UTF_8)
+    ///     .strict(true);
+    /// let src = b"\x83\x6E\x83\x8D\xFF"; // ハロ and a malformed byte in SHIFT_JIS
+    /// let buf = &mut [0u8; 128];
+    /// let (_, _, num_written, has_error) = transcoder.transcode(src, buf, true);
+    ///
+    /// assert!(has_error);
+    /// assert_eq!("ハロ".as_bytes(), &buf[..num_written]);
+    /// assert_eq!(4, transcoder.error().unwrap().offset());
+    /// ```
+    pub fn strict(mut self: Self, strict: bool) -> Self {
+        self.strict = strict;
+        return self;
+    }
+
+    /// Restricts the encoding detection to the specified encodings.
+    /// If the encoding `chardetng` picks is not one of them, the best-scoring one of them is used instead.
+    /// An encoding indicated by a BOM is accepted regardless of this setting.
+    /// Default is empty, which means all encodings are allowed.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let src = b"\xB4\xC1\xBB\xFA"; // 漢字 in EUC-JP
+    /// let transcoder = transcoding_rs::Transcoder::new(None, encoding_rs::UTF_8);
+    /// assert_eq!(encoding_rs::GBK, transcoder.detect(src, 100, 0, true).encoding.unwrap());
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(None, encoding_rs::UTF_8)
+    ///     .allowed_encodings(&[encoding_rs::SHIFT_JIS, encoding_rs::EUC_JP]);
+    /// assert_eq!(encoding_rs::EUC_JP, transcoder.detect(src, 100, 0, true).encoding.unwrap());
+    /// ```
+    pub fn allowed_encodings<E: Into<Encoding> + Copy>(mut self: Self, encodings: &[E]) -> Self {
+        self.allowed_encodings = encodings.iter().map(|e| (*e).into()).collect();
+        return self;
+    }
+
+    /// Sets the top-level domain (e.g. `jp`, `ru`, `cz`) passed to `chardetng` as a hint,
+    /// which helps to tell apart Cyrillic, CJK and Central European encodings.
+    /// The value is lower-cased and a leading period is removed.
+    /// If the value is not a valid top-level domain, it is ignored.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(None, encoding_rs::UTF_8)
+    ///     .tld_hint("jp");
+    /// let src = b"\x93\x8C\x8B\x9E"; // 東京 in SHIFT_JIS
+    /// assert_eq!(encoding_rs::SHIFT_JIS, transcoder.detect(src, 100, 0, true).encoding.unwrap());
+    /// ```
+    pub fn tld_hint(mut self: Self, tld: &str) -> Self {
+        if let Some(tld) = detection::normalize_tld(tld) {
+            self.tld_hint = Some(tld);
+        }
+        return self;
+    }
+
+    /// Sets the top-level domain hint by a language, which is an ISO 639-1 code like `ja` or `zh-TW`.
+    /// See `tld_for_language()` for the mapping.
+    /// Returns an error message if the language is unknown.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(None, encoding_rs::UTF_8)
+    ///     .language_hint("ja").unwrap();
+    /// let src = b"\x93\x8C\x8B\x9E"; // 東京 in SHIFT_JIS
+    /// assert_eq!(encoding_rs::SHIFT_JIS, transcoder.detect(src, 100, 0, true).encoding.unwrap());
+    /// ```
+    pub fn language_hint(self: Self, language: &str) -> Result<Self, String> {
+        return match detection::tld_for_language(language) {
+            Some(tld) => Ok(self.tld_hint(tld)),
+            None => Err(format!("Unknown language: {}", language)),
+        };
+    }
+
+    /// Sets whether to detect BOM-less UTF-16LE/BE by heuristics before guessing with `chardetng`.
+    /// Texts in UTF-16 have null bytes on one side of the code units for ASCII characters such as spaces and line breaks,
+    /// while texts in other encodings rarely have null bytes.
+    /// So the input is treated as UTF-16 if null bytes are found mostly at either even or odd offsets
+    /// and surrogates are paired properly. At least 8 code units are required.
+    /// Default is true.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let src = "Hello, world!".encode_utf16().flat_map(|u| u.to_le_bytes()).collect::<Vec<u8>>();
+    /// let transcoder = transcoding_rs::Transcoder::new(None, encoding_rs::UTF_8);
+    /// assert_eq!(encoding_rs::UTF_16LE, transcoder.detect(&src, 100, 0, true).encoding.unwrap());
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(None, encoding_rs::UTF_8)
+    ///     .bomless_heuristics(false);
+    /// assert_eq!(None, transcoder.detect(&src, 100, 0, true).encoding);
+    /// ```
+    pub fn bomless_heuristics(mut self: Self, enabled: bool) -> Self {
+        self.bomless_heuristics = enabled;
+        return self;
+    }
+
+    /// Sets whether `guess_and_transcode()` ranks the candidates in `detection()`.
+    /// Ranking asks `chardetng` again with each top-level domain hint and decodes the input with each candidate,
+    /// so only the single guess is done by default, and the candidates are left empty
+    /// unless the guessed encoding isn't allowed. `detect()` always ranks them.
+    /// Default is false.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let mut transcoder = transcoding_rs::Transcoder::new(None, encoding_rs::UTF_8)
+    ///     .rank_candidates(true);
+    /// let src = b"\x83\x6E\x83\x8D\x81\x5B"; // ハロー in SHIFT_JIS
+    /// transcoder.guess_and_transcode(src, &mut [0u8; 128], 6, 0, true);
+    /// assert_eq!(encoding_rs::SHIFT_JIS, transcoder.detection().unwrap().candidates[0].encoding);
+    /// ```
+    pub fn rank_candidates(mut self: Self, enabled: bool) -> Self {
+        self.ranks_candidates = enabled;
+        return self;
+    }
+
+    /// Sets how to handle characters that can't be mapped to the destination encoding.
+    /// Default is `UnmappablePolicy::Ncr`.
+    /// In the strict mode, this is ignored and unmappable characters are always treated as errors.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    /// use transcoding_rs::UnmappablePolicy;
+    ///
+    /// let mut transcoder = transcoding_rs::Transcoder::new(
+    ///     Some(encoding_rs::UTF_8),
+    ///     encoding_rs::SHIFT_JIS)
+    ///     .unmappable(UnmappablePolicy::Substitute('?'));
+    /// let src = "café".as_bytes();
+    /// let buf = &mut [0u8; 128];
+    /// let (_, _, num_written, has_unmappable) = transcoder.transcode(src, buf, true);
+    ///
+    /// assert!(has_unmappable);
+    /// assert_eq!(b"caf?", &buf[..num_written]);
+    /// ```
+    pub fn unmappable(mut self: Self, policy: UnmappablePolicy) -> Self {
+        self.unmappable = policy;
+        return self;
+    }
+
+    pub(crate) fn src_encoding(self: &Self) -> Option<Encoding> {
+        return self.src_encoding;
+    }
+
+    pub(crate) fn dst_encoding(self: &Self) -> Encoding {
+        return self.dst_encoding;
+    }
+
+    /// The error that stopped transcoding in the strict mode or by `UnmappablePolicy::Error`, if any.
+    /// Once an error is found, `transcode()` must not be called any more.
+    pub fn error(self: &Self) -> Option<&TranscodeError> {
+        return self.error.as_ref();
+    }
+
     /// Transcodes the source encoding to the destination encoding.
     /// Main functionality and usage are the same as decode_to_* or encode_from_* methods in [`encoding_rs`](https://github.com/hsivonen/encoding_rs).
     /// The key difference is that this function combined decode_to_* and encode_from_* methods
@@ -91,6 +352,7 @@ impl Transcoder {
     ///  - 3: The number of bytes read.
     ///  - 4: The number of bytes written.
     ///  - 4: Whether malformed byte sequences or unmappable characters are found while transcoding.
+    ///       In the strict mode, this is true if transcoding stopped by an error.
     ///
     /// # Example
     /// ```
@@ -107,6 +369,13 @@ impl Transcoder {
     /// assert_eq!("ハロー".as_bytes(), &buf[..num_written]);
     /// ```
     pub fn transcode(self: &mut Self, src: &[u8], dst: &mut [u8], last: bool) -> (enc::CoderResult, usize, usize, bool) {
+        if self.strict {
+            return self.transcode_strict(src, dst, last);
+        }
+        // nothing is unmappable in Unicode, so only the other encodings need to be transcoded byte by byte.
+        if self.unmappable == UnmappablePolicy::Error && self.encoder.is_some() && self.dst_encoding.bom().is_none() {
+            return self.transcode_by_byte(src, dst, last);
+        }
         let decoder = self.decoder.as_mut().expect("transcode() should be called after the source encoding is detected.");
         if self.dst_encoding == enc::UTF_8 {
             let (result, num_decoder_read, num_decoder_written, has_replacement) = decoder.decode_to_utf8(src, dst, last);
@@ -118,15 +387,20 @@ impl Transcoder {
             Transcoder::u16_to_u8(dst_u16, dst, num_decoder_written, self.dst_encoding == enc::UTF_16BE);
             return (result, num_decoder_read, num_decoder_written*2, has_replacement);
         } else {
-            let (decoder_result, num_decoder_read, num_decoder_written, has_replacement) =
-                decoder.decode_to_utf8(src, &mut self.decode_buffer, last);
+            // the encoder may still have unencoded bytes after the decoder has finished.
+            let (decoder_result, num_decoder_read, num_decoder_written, has_replacement) = if self.decoder_finished {
+                (enc::CoderResult::InputEmpty, 0, 0, false)
+            } else {
+                decoder.decode_to_utf8(src, &mut self.decode_buffer, last)
+            };
+            self.decoder_finished = last && decoder_result == enc::CoderResult::InputEmpty;
             self.unencoded_bytes.append(&mut self.decode_buffer[..num_decoder_written].to_vec());
             let encoder_input = unsafe {
                 str::from_utf8_unchecked(&self.unencoded_bytes)
             };
             let encoder = self.encoder.as_mut().unwrap();
             let (encoder_result, num_encoder_read, num_encoder_written, has_unmappable) =
-                encoder.encode_from_utf8(encoder_input, dst, last);
+                Transcoder::encode_from_utf8(encoder, self.unmappable, encoder_input, dst, last);
             self.unencoded_bytes = self.unencoded_bytes[num_encoder_read..].to_vec();
             let result = if decoder_result == enc::CoderResult::InputEmpty && encoder_result == enc::CoderResult::InputEmpty {
                 enc::CoderResult::InputEmpty
@@ -137,6 +411,145 @@ impl Transcoder {
         }
     }
 
+    fn transcode_strict(self: &mut Self, src: &[u8], dst: &mut [u8], last: bool) -> (enc::CoderResult, usize, usize, bool) {
+        let decoder = self.decoder.as_mut().expect("transcode() should be called after the source encoding is detected.");
+        let (decoder_result, num_read, num_written) = if self.dst_encoding == enc::UTF_8 {
+            decoder.decode_to_utf8_without_replacement(src, dst, last)
+        } else if self.dst_encoding == enc::UTF_16BE || self.dst_encoding == enc::UTF_16LE {
+            let dst_u16 = &mut vec![0u16; dst.len()/2];
+            let (decoder_result, num_decoder_read, num_decoder_written) =
+                decoder.decode_to_utf16_without_replacement(src, dst_u16, last);
+            Transcoder::u16_to_u8(dst_u16, dst, num_decoder_written, self.dst_encoding == enc::UTF_16BE);
+            (decoder_result, num_decoder_read, num_decoder_written*2)
+        } else {
+            return self.transcode_by_byte(src, dst, last);
+        };
+        self.num_read_total += num_read;
+        return match decoder_result {
+            enc::DecoderResult::InputEmpty => (enc::CoderResult::InputEmpty, num_read, num_written, false),
+            enc::DecoderResult::OutputFull => (enc::CoderResult::OutputFull, num_read, num_written, false),
+            enc::DecoderResult::Malformed(bad, extra) => {
+                let offset = self.num_read_total.saturating_sub(bad as usize + extra as usize);
+                self.error = Some(TranscodeError::Malformed { offset });
+                (enc::CoderResult::OutputFull, num_read, num_written, true)
+            },
+        };
+    }
+
+    // Since the encoder reads decoded UTF-8 texts, the offset of an unmappable character in the input is unknown
+    // if a bunch of bytes are decoded at once.
+    // So this decodes the input byte by byte to keep track of where each character starts.
+    // Malformed byte sequences are errors only in the strict mode, while unmappable characters always are.
+    // If `dst` has less room than a byte may be transcoded into, the output goes through the staging buffer,
+    // which is written to `dst` first in the next call.
+    fn transcode_by_byte(self: &mut Self, src: &[u8], dst: &mut [u8], last: bool) -> (enc::CoderResult, usize, usize, bool) {
+        const MAX_WRITTEN_PER_BYTE: usize = 32; // enough for up to 2 characters and an escape sequence.
+        let mut num_written = Transcoder::unstage(&mut self.staged, dst);
+        if !self.staged.is_empty() {
+            return (enc::CoderResult::OutputFull, 0, num_written, false);
+        }
+        if self.flushed_by_byte {
+            return (enc::CoderResult::InputEmpty, 0, num_written, false);
+        }
+        let decoder = self.decoder.as_mut().unwrap();
+        let encoder = self.encoder.as_mut().unwrap();
+        let decoded = &mut [0u8; MAX_WRITTEN_PER_BYTE];
+        let staging = &mut [0u8; MAX_WRITTEN_PER_BYTE];
+        let mut num_read = 0;
+        let mut had_replacement = false;
+        loop {
+            let flush = num_read == src.len();
+            if flush && !last {
+                return (enc::CoderResult::InputEmpty, num_read, num_written, had_replacement);
+            }
+            let stages = dst.len() - num_written < MAX_WRITTEN_PER_BYTE;
+            if stages && (num_written > 0 || !self.staged.is_empty()) {
+                return (enc::CoderResult::OutputFull, num_read, num_written, had_replacement);
+            }
+            let byte = if flush { &src[..0] } else { &src[num_read..num_read+1] };
+            let (num_decoder_read, num_decoded) = if self.strict {
+                let (decoder_result, num_decoder_read, num_decoded) =
+                    decoder.decode_to_utf8_without_replacement(byte, decoded, flush);
+                if let enc::DecoderResult::Malformed(bad, extra) = decoder_result {
+                    let offset = (self.num_read_total + num_decoder_read).saturating_sub(bad as usize + extra as usize);
+                    self.error = Some(TranscodeError::Malformed { offset });
+                    return (enc::CoderResult::OutputFull, num_read + num_decoder_read, num_written, true);
+                }
+                (num_decoder_read, num_decoded)
+            } else {
+                let (_, num_decoder_read, num_decoded, has_replacement) = decoder.decode_to_utf8(byte, decoded, flush);
+                had_replacement = had_replacement || has_replacement;
+                (num_decoder_read, num_decoded)
+            };
+            num_read += num_decoder_read;
+            self.num_read_total += num_decoder_read;
+            let encoder_input = unsafe {
+                str::from_utf8_unchecked(&decoded[..num_decoded])
+            };
+            let encoder_output: &mut [u8] = if stages { staging } else { &mut dst[num_written..] };
+            let (encoder_result, _, num_encoder_written) =
+                encoder.encode_from_utf8_without_replacement(encoder_input, encoder_output, flush);
+            if stages {
+                self.staged = staging[..num_encoder_written].to_vec();
+                num_written += Transcoder::unstage(&mut self.staged, dst);
+            } else {
+                num_written += num_encoder_written;
+            }
+            if let enc::EncoderResult::Unmappable(character) = encoder_result {
+                self.error = Some(TranscodeError::Unmappable { offset: self.char_offset, character });
+                return (enc::CoderResult::OutputFull, num_read, num_written, true);
+            }
+            if num_decoded > 0 {
+                self.char_offset = self.num_read_total;
+            }
+            if flush {
+                self.flushed_by_byte = true;
+                let result = if self.staged.is_empty() { enc::CoderResult::InputEmpty } else { enc::CoderResult::OutputFull };
+                return (result, num_read, num_written, had_replacement);
+            }
+        }
+    }
+
+    // Writes the staged output to `dst` as far as it fits, returning the number of bytes written.
+    fn unstage(staged: &mut Vec<u8>, dst: &mut [u8]) -> usize {
+        let n = std::cmp::min(staged.len(), dst.len());
+        dst[..n].copy_from_slice(&staged[..n]);
+        staged.drain(..n);
+        return n;
+    }
+
+    // Same as `Encoder::encode_from_utf8()` except that unmappable characters are handled by the policy.
+    fn encode_from_utf8(encoder: &mut Encoder, policy: UnmappablePolicy, src: &str, dst: &mut [u8], last: bool)
+        -> (enc::CoderResult, usize, usize, bool) {
+        if policy == UnmappablePolicy::Ncr || policy == UnmappablePolicy::Error { // errors are handled by transcode_by_byte()
+            return encoder.encode_from_utf8(src, dst, last);
+        }
+        let mut num_read = 0;
+        let mut num_written = 0;
+        let mut had_unmappable = false;
+        loop {
+            let (encoder_result, num_encoder_read, num_encoder_written) =
+                encoder.encode_from_utf8_without_replacement(&src[num_read..], &mut dst[num_written..], last);
+            num_read += num_encoder_read;
+            num_written += num_encoder_written;
+            match encoder_result {
+                enc::EncoderResult::InputEmpty => return (enc::CoderResult::InputEmpty, num_read, num_written, had_unmappable),
+                enc::EncoderResult::OutputFull => return (enc::CoderResult::OutputFull, num_read, num_written, had_unmappable),
+                enc::EncoderResult::Unmappable(c) => {
+                    had_unmappable = true;
+                    let replacement = policy.replacement(c);
+                    if dst.len() - num_written < replacement.len() + 8 { // 8 bytes for escape sequences of stateful encodings
+                        num_read -= c.len_utf8(); // the character will be pushed again in the next call.
+                        return (enc::CoderResult::OutputFull, num_read, num_written, had_unmappable);
+                    }
+                    let (_, _, num_replacement_written) =
+                        encoder.encode_from_utf8_without_replacement(&replacement, &mut dst[num_written..], false);
+                    num_written += num_replacement_written;
+                },
+            }
+        }
+    }
+
     /// Guesses the source encoding and try to transcode input.
     /// This method should be called once before the `transcode()` method if `Transcode` is created with no source encoding provided.
     /// This method must not be called after `transcode()` is called.
@@ -175,41 +588,14 @@ impl Transcoder {
     /// assert_eq!("ハロー".as_bytes(), &buf[..num_written]);
     /// ```
     pub fn guess_and_transcode(self: &mut Self, src: &[u8], dst: & mut [u8], non_ascii_to_guess: usize, non_text_threshold: u8, last: bool)
-        -> (Option<&'static enc::Encoding>, enc::CoderResult, usize, usize, bool) {
-
-        let mut detector = cd::EncodingDetector::new();
+        -> (Option<Encoding>, enc::CoderResult, usize, usize, bool) {
 
-        // guess the encoding and get a decoder
-        let mut decoder = match enc::Encoding::for_bom(src) { // BOM sniffing
-            Some(found_bom) => {
-                let (encoding,_) = found_bom;
-                encoding.new_decoder()
-            },
-            None => { // guess BOMless encodings
-                let num_read = src.len();
-                let mut non_ascii_cnt = 0;
-                let mut num_fed = 0;
-                let mut exhausted;
-                for b in src.iter() {
-                    num_fed+=1;
-                    exhausted = num_read == num_fed;
-                    let is_non_ascii = detector.feed(&[*b], last && exhausted);
-                    let is_non_text = Transcoder::is_non_text(&(*b as char));
-                    if is_non_ascii || is_non_text {
-                        non_ascii_cnt+=1;
-                        if non_ascii_cnt > non_ascii_to_guess {
-                            break;
-                        }
-                    }
-                }
-                let top_level_domain = None;
-                let allow_utf8 = true;
-                detector.guess(top_level_domain, allow_utf8).new_decoder()
-            },
-        };
-        let (ok, coder_result, decoder_read, decoder_written, has_replacement)
+        let (encoding, bom, candidates) = self.rank(src, non_ascii_to_guess, last, self.ranks_candidates);
+        let mut decoder = encoding.new_decoder();
+        let (judged, coder_result, decoder_read, decoder_written, has_replacement)
             = Transcoder::try_transcode(self, &mut decoder, last, src, dst, non_text_threshold);
-        if ok {
+        self.detection = Some(Transcoder::new_detection(encoding, bom, candidates, judged));
+        if self.detection.as_ref().unwrap().rejection.is_none() {
             self.src_encoding = Some(decoder.encoding());
             self.decoder = Some(decoder);
             return (self.src_encoding, coder_result, decoder_read, decoder_written, has_replacement);
@@ -220,9 +606,226 @@ impl Transcoder {
         }
     }
 
+    /// Guesses the source encoding in the same way as `guess_and_transcode()` without transcoding,
+    /// and returns the details of the detection.
+    ///
+    /// # Parameters
+    ///  - src: The input to be examined.
+    ///  - non_ascii_to_guess: The number of non-ASCII characters to be used to guess the encoding.
+    ///  - non_text_threshold: The threshold to determine the guess is failed.
+    ///                        The value should be specified in percentage.
+    ///  - last: Specify true if the input has reached EOF, or otherwise false.
+    ///
+    /// # Example
+    /// ```
+    /// use encoding_rs;
+    ///
+    /// let transcoder = transcoding_rs::Transcoder::new(None, encoding_rs::UTF_8);
+    /// let src = b"\x83\x6E\x83\x8D\x81\x5B"; // ハロー in SHIFT_JIS
+    /// let detection = transcoder.detect(src, 6, 0, true);
+    ///
+    /// assert_eq!(encoding_rs::SHIFT_JIS, detection.encoding.unwrap());
+    /// assert_eq!(encoding_rs::SHIFT_JIS, detection.candidates[0].encoding);
+    /// assert!(detection.rejection.is_none());
+    /// ```
+    pub fn detect(self: &Self, src: &[u8], non_ascii_to_guess: usize, non_text_threshold: u8, last: bool) -> Detection {
+        let (encoding, bom, candidates) = self.rank(src, non_ascii_to_guess, last, true);
+        let judged = Transcoder::judge(&Transcoder::decode(encoding, src, last), non_text_threshold);
+        return Transcoder::new_detection(encoding, bom, candidates, judged);
+    }
+
+    /// The details of the detection done by `guess_and_transcode()`.
+    pub fn detection(self: &Self) -> Option<&Detection> {
+        return self.detection.as_ref();
+    }
+
+    // Guesses the encoding and ranks the candidates if `ranks` is true or the guessed encoding isn't allowed.
+    // Since chardetng only tells the best one, the candidates are collected by asking it again with
+    // various top-level domain hints, and scored by how many hints picked them and how few non-text characters are found.
+    fn rank(self: &Self, src: &[u8], non_ascii_to_guess: usize, last: bool, ranks: bool) -> (Encoding, bool, Vec<Candidate>) {
+        if let Some((encoding, _)) = Encoding::for_bom(src) { // BOM sniffing
+            if ! ranks {
+                return (encoding, true, vec![]);
+            }
+            let non_text_ratio = Transcoder::non_text_ratio(&Transcoder::decode(encoding, src, last));
+            let candidate = Candidate { encoding, score: 1.0 - non_text_ratio / 100.0, non_text_ratio };
+            return (encoding, true, vec![candidate]);
+        }
+        let bomless_unicode = Transcoder::guess_bomless_utf32(src).or_else(|| Transcoder::guess_bomless_utf16(src));
+        if let (true, Some(encoding)) = (self.bomless_heuristics, bomless_unicode) {
+            if self.is_allowed(encoding) {
+                if ! ranks {
+                    return (encoding, false, vec![]);
+                }
+                let non_text_ratio = Transcoder::non_text_ratio(&Transcoder::decode(encoding, src, last));
+                let candidate = Candidate { encoding, score: 1.0 - non_text_ratio / 100.0, non_text_ratio };
+                return (encoding, false, vec![candidate]);
+            }
+        }
+        // guess BOMless encodings
+        let mut detector = cd::EncodingDetector::new();
+        let num_read = src.len();
+        let mut non_ascii_cnt = 0;
+        let mut num_fed = 0;
+        let mut exhausted;
+        for b in src.iter() {
+            num_fed+=1;
+            exhausted = num_read == num_fed;
+            let is_non_ascii = detector.feed(&[*b], last && exhausted);
+            let is_non_text = Transcoder::is_non_text(&(*b as char));
+            if is_non_ascii || is_non_text {
+                non_ascii_cnt+=1;
+                if non_ascii_cnt > non_ascii_to_guess {
+                    break;
+                }
+            }
+        }
+        let top_level_domain = self.tld_hint.as_ref().map(|tld| tld.as_bytes());
+        let allow_utf8 = self.is_allowed(Encoding::from(enc::UTF_8));
+        let guessed = Encoding::from(detector.guess(top_level_domain, allow_utf8));
+        if ! ranks && self.is_allowed(guessed) {
+            return (guessed, false, vec![]);
+        }
+        let mut votes = vec![(guessed, 0)];
+        for tld in constants::TLD_HINTS.iter() {
+            let encoding = Encoding::from(detector.guess(Some(tld.as_bytes()), allow_utf8));
+            match votes.iter_mut().find(|(e, _)| *e == encoding) {
+                Some((_, n)) => *n += 1,
+                None => votes.push((encoding, 1)),
+            }
+        }
+        for encoding in self.allowed_encodings.iter() { // allowed encodings are candidates even if no hints picked them
+            if votes.iter().all(|(e, _)| e != encoding) {
+                votes.push((*encoding, 0));
+            }
+        }
+        votes.retain(|(e, _)| self.is_allowed(*e));
+        let mut candidates: Vec<Candidate> = votes.iter().map(|(encoding, n)| {
+            let non_text_ratio = Transcoder::non_text_ratio(&Transcoder::decode(*encoding, src, last));
+            let guessed_score = if *encoding == guessed { 0.5 } else { 0.0 };
+            let vote_score = 0.5 * (*n as f64) / (constants::TLD_HINTS.len() as f64);
+            Candidate { encoding: *encoding, score: (guessed_score + vote_score) * (1.0 - non_text_ratio / 100.0), non_text_ratio }
+        }).collect();
+        candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.non_text_ratio.partial_cmp(&b.non_text_ratio).unwrap_or(std::cmp::Ordering::Equal)));
+        let encoding = if self.is_allowed(guessed) { guessed } else { candidates[0].encoding };
+        return (encoding, false, candidates);
+    }
+
+    // Texts in UTF-32 have at least one null byte in every code unit, and every unit must be a Unicode scalar value.
+    pub(crate) fn guess_bomless_utf32(src: &[u8]) -> Option<Encoding> {
+        const MIN_UNITS: usize = 4;
+        let num_units = src.len() / 4;
+        if num_units < MIN_UNITS {
+            return None;
+        }
+        let units = || src[..num_units*4].chunks(4).map(|unit| [unit[0], unit[1], unit[2], unit[3]]);
+        if units().all(|unit| unit[3] == 0 && char::from_u32(u32::from_le_bytes(unit)).is_some()) {
+            return Some(Encoding::Utf32Le);
+        }
+        if units().all(|unit| unit[0] == 0 && char::from_u32(u32::from_be_bytes(unit)).is_some()) {
+            return Some(Encoding::Utf32Be);
+        }
+        return None;
+    }
 
-    fn try_transcode(self: &mut Self, decoder: &mut enc::Decoder, last: bool, src: &[u8], dst: &mut [u8], non_text_threshold: u8)
-        -> (bool, enc::CoderResult, usize, usize, bool) {
+    // Texts in UTF-16 have null bytes on the high side of ASCII characters,
+    // and the high bytes of the other characters are mostly the same if the texts are in a single script.
+    // So a large share of code units need to have either a null byte or the most common high byte there.
+    pub(crate) fn guess_bomless_utf16(src: &[u8]) -> Option<Encoding> {
+        const MIN_UNITS: usize = 8;
+        const MIN_ZEROS: usize = 4;
+        let num_units = src.len() / 2;
+        if num_units < MIN_UNITS {
+            return None;
+        }
+        let units = &src[..num_units*2];
+        let zeros_even = units.iter().step_by(2).filter(|b| **b == 0).count();
+        let zeros_odd = units.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+        let (encoding, zeros, other_zeros) = if zeros_odd > zeros_even {
+            (enc::UTF_16LE, zeros_odd, zeros_even)
+        } else {
+            (enc::UTF_16BE, zeros_even, zeros_odd)
+        };
+        if zeros < MIN_ZEROS || zeros < other_zeros * 4 { // null bytes need to be mostly on one side
+            return None;
+        }
+        if zeros * 4 < num_units { // ASCII characters are few, so the rest need to share the high byte
+            let high_bytes = units.iter().skip(if encoding == enc::UTF_16LE { 1 } else { 0 }).step_by(2);
+            let counts = &mut [0usize; 256];
+            high_bytes.filter(|b| **b != 0).for_each(|b| counts[*b as usize] += 1);
+            let most_common = counts.iter().max().copied().unwrap_or(0);
+            if (zeros + most_common) * 2 < num_units {
+                return None;
+            }
+        }
+        let mut expects_low = false;
+        for unit in units.chunks(2) {
+            let unit = if encoding == enc::UTF_16BE {
+                u16::from_be_bytes([unit[0], unit[1]])
+            } else {
+                u16::from_le_bytes([unit[0], unit[1]])
+            };
+            let is_low = (0xDC00..0xE000).contains(&unit);
+            if expects_low != is_low { // unpaired surrogate
+                return None;
+            }
+            expects_low = (0xD800..0xDC00).contains(&unit);
+        }
+        return Some(Encoding::from(encoding));
+    }
+
+    fn is_allowed(self: &Self, encoding: Encoding) -> bool {
+        return self.allowed_encodings.is_empty() || self.allowed_encodings.contains(&encoding);
+    }
+
+    fn new_detection(encoding: Encoding, bom: bool, candidates: Vec<Candidate>, judged: Result<f64, Rejection>) -> Detection {
+        return match judged {
+            Ok(non_text_ratio) => Detection { encoding: Some(encoding), bom, candidates, non_text_ratio, rejection: None },
+            Err(rejection) => {
+                let non_text_ratio = match rejection {
+                    Rejection::NonText { ratio, .. } => ratio,
+                    Rejection::NoText => 0.0,
+                };
+                Detection { encoding: None, bom, candidates, non_text_ratio, rejection: Some(rejection) }
+            },
+        };
+    }
+
+    fn decode(encoding: Encoding, src: &[u8], last: bool) -> String {
+        let mut decoder = encoding.new_decoder();
+        let mut decoded = vec![0u8; decoder.max_utf8_buffer_length(src.len()).unwrap_or(src.len()*3)];
+        let (_, _, num_written, _) = decoder.decode_to_utf8(src, &mut decoded, last);
+        decoded.truncate(num_written);
+        return String::from_utf8(decoded).unwrap_or_default();
+    }
+
+    fn non_text_ratio(decoded: &str) -> f64 {
+        let num_chars = decoded.chars().count();
+        if num_chars == 0 {
+            return 0.0;
+        }
+        let non_text_cnt = decoded.chars().filter(Transcoder::is_non_text).count();
+        return (non_text_cnt * 100) as f64 / num_chars as f64;
+    }
+
+    // Judges whether the guess is acceptable by the occurrence of non-text characters,
+    // returning the percentage of them.
+    fn judge(decoded: &str, non_text_threshold: u8) -> Result<f64, Rejection> {
+        let num_chars = decoded.chars().count();
+        if num_chars == 0 {
+            return Err(Rejection::NoText);
+        }
+        let non_text_cnt = decoded.chars().filter(Transcoder::is_non_text).count();
+        let ratio = (non_text_cnt * 100) as f64 / num_chars as f64;
+        if (non_text_threshold as usize) < (non_text_cnt * 100 / num_chars) {
+            return Err(Rejection::NonText { ratio, threshold: non_text_threshold });
+        }
+        return Ok(ratio);
+    }
+
+    fn try_transcode(self: &mut Self, decoder: &mut Decoder, last: bool, src: &[u8], dst: &mut [u8], non_text_threshold: u8)
+        -> (Result<f64, Rejection>, enc::CoderResult, usize, usize, bool) {
         let decode_buffer = if self.dst_encoding == enc::UTF_8 {
             &mut (*dst)
         } else {
@@ -232,22 +835,12 @@ impl Transcoder {
         let decode_buffer_str = unsafe{
             str::from_utf8_unchecked_mut(&mut decode_buffer[..num_decoder_written])
         };
-        let mut non_text_cnt = 0;
-        let guess_failed = if 0 < decode_buffer_str.chars().count() {
-            for c in decode_buffer_str.chars() {
-                if Transcoder::is_non_text(&c) {
-                    non_text_cnt+=1;
-                }
-            }
-            (non_text_threshold as usize) < (non_text_cnt * 100 / decode_buffer_str.chars().count())
-        } else {
-            true
-        };
-        if guess_failed {
-            return (false, decoder_result, num_decoder_read, num_decoder_written, has_replacement);
+        let judged = Transcoder::judge(decode_buffer_str, non_text_threshold);
+        if judged.is_err() {
+            return (judged, decoder_result, num_decoder_read, num_decoder_written, has_replacement);
         }
         if self.dst_encoding == enc::UTF_8 {
-            return (true, decoder_result, num_decoder_read, num_decoder_written, has_replacement);
+            return (judged, decoder_result, num_decoder_read, num_decoder_written, has_replacement);
         }
         if self.dst_encoding == enc::UTF_16BE || self.dst_encoding == enc::UTF_16LE {
             self.decoder = Some(decoder.encoding().new_decoder()); // the decoder was used once to check the guess result, so we need a new one.
@@ -256,22 +849,23 @@ impl Transcoder {
             let (result, num_decoder_read, num_decoder_written, has_replacement) =
                 new_decoder.decode_to_utf16(src, dst_u16, last);
             Transcoder::u16_to_u8(dst_u16, dst, num_decoder_written, self.dst_encoding == enc::UTF_16BE);
-            return (true, result, num_decoder_read, num_decoder_written*2, has_replacement);
+            return (judged, result, num_decoder_read, num_decoder_written*2, has_replacement);
         } else {
+            self.decoder_finished = last && decoder_result == enc::CoderResult::InputEmpty;
             self.unencoded_bytes.append(&mut decode_buffer[..num_decoder_written].to_vec());
             let encoder_input = unsafe {
                 str::from_utf8_unchecked(&self.unencoded_bytes)
             };
             let encoder = self.encoder.as_mut().unwrap();
             let (encoder_result, num_encoder_read, num_encoder_written, has_unmappable) =
-                encoder.encode_from_utf8(encoder_input, dst, last);
+                Transcoder::encode_from_utf8(encoder, self.unmappable, encoder_input, dst, last);
             self.unencoded_bytes = self.unencoded_bytes[num_encoder_read..].to_vec();
             let coder_result = if decoder_result == enc::CoderResult::InputEmpty && encoder_result == enc::CoderResult::InputEmpty {
                 enc::CoderResult::InputEmpty
             } else {
                 enc::CoderResult::OutputFull
             };
-            return (true, coder_result, num_decoder_read, num_encoder_written, has_replacement || has_unmappable);
+            return (judged, coder_result, num_decoder_read, num_encoder_written, has_replacement || has_unmappable);
         }
     }
 
@@ -332,6 +926,9 @@ mod tests {
 
     test_guess!(guess_utf16le_utf8     , "utf16le_BOM_th.txt"  , "utf8_th.txt"     , "utf8");
     test_guess!(guess_utf16be_utf8     , "utf16be_BOM_th.txt"  , "utf8_th.txt"     , "utf8");
+    test_guess!(guess_utf32le_utf8     , "utf32le_BOM_th.txt"  , "utf8_th.txt"     , "utf8");
+    test_guess!(guess_utf32be_utf8     , "utf32be_BOM_th.txt"  , "utf8_th.txt"     , "utf8");
+    test_guess!(guess_utf32le_nobom_utf8, "utf32le_th.txt"     , "utf8_th.txt"     , "utf8");
     test_guess!(guess_sjis_utf8        , "sjis_ja.txt"         , "utf8_ja.txt"     , "utf8");
     test_guess!(guess_eucjp_utf8       , "euc-jp_ja.txt"       , "utf8_ja.txt"     , "utf8");
     test_guess!(guess_iso2022jp_utf8   , "iso-2022-jp_ja.txt"  , "utf8_ja.txt"     , "utf8");
@@ -341,6 +938,8 @@ mod tests {
     test_guess!(guess_euckr_utf8       , "euc-kr_ko.txt"       , "utf8_ko.txt"     , "utf8");
     test_guess!(guess_koi8r_utf8       , "koi8-r_ru.txt"       , "utf8_ru.txt"     , "utf8");
     test_guess!(guess_windows1252_utf8 , "windows-1252_es.txt" , "utf8_es.txt"     , "utf8");
+    test_guess!(guess_utf16le_nobom_utf8 , "utf16le_th.txt"    , "utf8_th.txt"     , "utf8");
+    test_guess!(guess_utf16be_nobom_utf8 , "utf16be_th.txt"    , "utf8_th.txt"     , "utf8");
 
     test_guess!(guess_utf8_utf16le     , "utf8_th.txt"     , "utf16le_th.txt"      , "utf-16le"     );
     test_guess!(guess_utf8_utf16be     , "utf8_th.txt"     , "utf16be_th.txt"      , "utf-16be"     );
@@ -366,13 +965,104 @@ mod tests {
         assert!(o.0.is_none());
     }
 
+    #[test]
+    fn detect_candidates() {
+        let file_handle = &mut std::fs::File::open("../test_data/windows-1252_es.txt").unwrap();
+        let input = &mut Vec::new();
+        file_handle.read_to_end(input).unwrap();
+        let t = super::Transcoder::new(None, super::enc::UTF_8);
+        let detection = t.detect(input, 100, 0, true);
+        assert_eq!(Some(super::enc::WINDOWS_1252.into()), detection.encoding);
+        assert!(!detection.bom);
+        assert_eq!(super::enc::WINDOWS_1252, detection.candidates[0].encoding);
+        assert!(detection.candidates.iter().any(|c| c.encoding == super::enc::ISO_8859_2));
+        assert!(detection.candidates.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(2, detection.top(2).len());
+    }
+
+    #[test]
+    fn detect_rejection() {
+        let file_handle = &mut std::fs::File::open("../test_data/binary.jpeg").unwrap();
+        let input = &mut [0u8; 500];
+        file_handle.read(input).unwrap();
+        let t = &mut super::Transcoder::new(None, super::enc::UTF_8);
+        let output = &mut [0u8; 1024];
+        let _ = t.guess_and_transcode(input, output, 100, 0, false);
+        let detection = t.detection().unwrap();
+        assert_eq!(None, detection.encoding);
+        assert!(matches!(detection.rejection, Some(super::Rejection::NonText { threshold: 0, .. })));
+        assert!(detection.non_text_ratio > 0.0);
+        let t = super::Transcoder::new(None, super::enc::UTF_8);
+        assert_eq!(Some(super::Rejection::NoText), t.detect(b"\xFF\xFE", 100, 0, true).rejection);
+    }
+
+    #[test]
+    fn detect_bomless_utf16() {
+        let t = super::Transcoder::new(None, super::enc::UTF_8);
+        let detect = |src: &[u8]| t.detect(src, 100, 0, true).encoding.and_then(|e| e.as_standard()); // UTF-32 is covered below
+        assert_eq!(Some(super::enc::UTF_16BE), detect(b"\x00a\x00b\x00c\x00d\x00e\x00f\x00g\x00h"));
+        assert_eq!(Some(super::enc::UTF_16LE), detect(b"\x3D\xD8\x00\xDE \x00a\x00b\x00c\x00d\x00e\x00f\x00")); // 😀 in the first 2 units
+        assert_eq!(None, detect(b"a\x00b\x00c\x00d\x00e\x00f\x00g\x00")); // too short
+        assert_eq!(None, detect(b"\x00\xDEa\x00b\x00c\x00d\x00e\x00f\x00g\x00h\x00")); // unpaired low surrogate
+        assert_eq!(None, detect(b"a\x00\x00\x00b\x00\x00\x00c\x00\x00\x00d\x00\x00\x00")); // null bytes on both sides
+        assert_eq!(None, detect(b"hello world, this is plain text\x00 and more!")); // a stray null byte in ASCII
+        assert_eq!(None, detect(b"hello\x00 world\x00, this\x00 is plain\x00 text and more!")); // ASCII as high bytes
+        assert_eq!(Some(super::enc::UTF_16LE), detect(&std::fs::read("../test_data/utf16le_th.txt").unwrap()));
+    }
+
+    #[test]
+    fn detect_bomless_utf32() {
+        let t = super::Transcoder::new(None, super::enc::UTF_8);
+        let detect = |src: &[u8]| t.detect(src, 100, 0, true).encoding;
+        assert_eq!(Some(super::Encoding::Utf32Le), detect(b"a\x00\x00\x00b\x00\x00\x00c\x00\x00\x00\x00\xF6\x01\x00"));
+        assert_eq!(Some(super::Encoding::Utf32Be), detect(b"\x00\x00\x00a\x00\x00\x00b\x00\x00\x00c\x00\x00\x00d"));
+        assert_eq!(None, detect(b"a\x00\x00\x00b\x00\x00\x00c\x00\x00\x00")); // too short
+        assert_eq!(None, detect(b"a\x00\x00\x00b\x00\x00\x00c\x00\x00\x00\x00\x00\x11\x00")); // out of the Unicode range
+        assert_eq!(Some(super::Encoding::Utf32Le), detect(b"a\x00\x00\x00b\x00\x00\x00c\x00\x00\x00d\x00\x00\x00")); // not UTF-16 with null bytes on both sides
+        assert_eq!(None, detect(b"a\x00\x00\x00b\x00\x00\x01c\x00\x00\x00d\x00\x00\x00")); // neither UTF-16 nor UTF-32
+    }
+
+    #[test]
+    fn detect_allowed_encodings() {
+        let src = b"\x93\x8C\x8B\x9E"; // 東京 in SHIFT_JIS
+        let t = super::Transcoder::new(None, super::enc::UTF_8);
+        assert_eq!(Some(super::enc::WINDOWS_1250.into()), t.detect(src, 100, 0, true).encoding);
+        let t = &mut super::Transcoder::new(None, super::enc::UTF_8)
+            .allowed_encodings(&[super::enc::UTF_8, super::enc::EUC_JP, super::enc::SHIFT_JIS])
+            .rank_candidates(true);
+        let output = &mut [0u8; 1024];
+        let (enc, _, _, num_written, _) = t.guess_and_transcode(src, output, 100, 0, true);
+        assert_eq!(Some(super::enc::SHIFT_JIS.into()), enc);
+        assert_eq!("東京".as_bytes(), &output[..num_written]);
+        let candidates = &t.detection().unwrap().candidates;
+        assert_eq!(3, candidates.len());
+        assert_eq!(super::enc::SHIFT_JIS, candidates[0].encoding);
+    }
+
+    #[test]
+    fn detect_without_ranking() {
+        let src = b"\x93\x8C\x8B\x9E"; // 東京 in SHIFT_JIS
+        let t = &mut super::Transcoder::new(None, super::enc::UTF_8).tld_hint("jp");
+        let (enc, _, _, _, _) = t.guess_and_transcode(src, &mut [0u8; 1024], 100, 0, true);
+        assert_eq!(Some(super::enc::SHIFT_JIS.into()), enc);
+        assert!(t.detection().unwrap().candidates.is_empty());
+    }
+
+    #[test]
+    fn language_hint_unknown() {
+        let t = super::Transcoder::new(None, super::enc::UTF_8);
+        assert_eq!("Unknown language: xx", t.language_hint("xx").err().unwrap());
+        let detector = crate::I18nReaderEncodingDetector::new();
+        assert_eq!("Unknown language: xx", detector.language_hint("xx").err().unwrap());
+    }
+
     macro_rules! transcode_test {
         ($name:ident, $dec:expr, $enc:expr, $srcbytes:expr, $dst:expr) => {
             #[test]
             fn $name() {
-                let dec = super::enc::Encoding::for_label($dec.as_bytes());
-                let enc = super::enc::Encoding::for_label($enc.as_bytes());
-                let mut t = super::Transcoder::new(dec, enc.unwrap());
+                let dec = super::Encoding::for_label($dec.as_bytes());
+                let enc = super::Encoding::for_label($enc.as_bytes());
+                let mut t = super::Transcoder::for_encodings(dec, enc.unwrap());
                 // let output = &mut [0u8; 14]; // encoder seems to need at least 14 bytes
                 let output = &mut [0u8; 140]; // encoder seems to need at least 14 bytes
                 let (_,_,written,_) = t.transcode($srcbytes, output, false);
@@ -392,6 +1082,8 @@ mod tests {
     transcode_test!(trans_same_sjis       ,        "sjis"  ,           "sjis" , b"\x84\x47" , b"\x84\x47"); // Ж
     transcode_test!(trans_same_eucjp      ,       "euc-jp" ,         "euc-jp" , b"\xA7\xA8" , b"\xA7\xA8"); // Ж
     transcode_test!(trans_same_latin1     ,      "latin1"  ,         "latin1" , b"\xA9"     , b"\xA9"    ); // ©
+    transcode_test!(trans_same_utf32le    ,     "utf-32le" ,       "utf-32le" , b"\x16\x04\x00\x00" , b"\x16\x04\x00\x00"); // Ж
+    transcode_test!(trans_same_utf32be    ,     "utf-32be" ,       "utf-32be" , b"\x00\x00\x04\x16" , b"\x00\x00\x04\x16"); // Ж
 
     transcode_test!(trans_diff_utf8_utf16le       ,        "utf-8" ,       "utf-16le" , b"\xD0\x96"     , b"\x16\x04"); // Ж
     transcode_test!(trans_diff_utf16le_utf16be    ,     "utf-16le" ,       "utf-16be" , b"\x16\x04"     , b"\x04\x16"); // Ж
@@ -403,4 +1095,120 @@ mod tests {
     transcode_test!(trans_diff_sjis_eucjp         ,        "sjis"  ,         "euc-jp" , b"\x84\x47"     , b"\xA7\xA8"); // Ж
     transcode_test!(trans_diff_eucjp_latin1       ,       "euc-jp" ,         "latin1" , b"\x8F\xA2\xED" , b"\xA9"    ); // ©
     transcode_test!(trans_diff_latin1_utf8        ,      "latin1"  ,          "utf-8" , b"\xA9"         , b"\xC2\xA9"); // ©
+    transcode_test!(trans_diff_utf8_utf32be       ,        "utf-8" ,       "utf-32be" , b"\xD0\x96"     , b"\x00\x00\x04\x16"); // Ж
+    transcode_test!(trans_diff_utf32be_utf32le    ,     "utf-32be" ,       "utf-32le" , b"\x00\x00\x04\x16" , b"\x16\x04\x00\x00"); // Ж
+    transcode_test!(trans_diff_utf32le_utf16be    ,     "utf-32le" ,       "utf-16be" , b"\x16\x04\x00\x00" , b"\x04\x16"); // Ж
+    transcode_test!(trans_diff_utf32le_sjis       ,     "utf-32"   ,           "sjis" , b"\x16\x04\x00\x00" , b"\x84\x47"); // Ж
+    transcode_test!(trans_diff_utf16le_utf32le    ,     "utf-16le" ,       "utf-32le" , b"\x3D\xD8\x00\xDE" , b"\x00\xF6\x01\x00"); // 😀
+
+    macro_rules! strict_test {
+        ($name:ident, $dec:expr, $enc:expr, $srcbytes:expr, $dst:expr, $err:expr) => {
+            #[test]
+            fn $name() {
+                let dec = super::Encoding::for_label($dec.as_bytes());
+                let enc = super::Encoding::for_label($enc.as_bytes());
+                let mut t = super::Transcoder::for_encodings(dec, enc.unwrap()).strict(true);
+                let output = &mut [0u8; 140];
+                let (_,_,written,has_error) = t.transcode($srcbytes, output, true);
+                let err: Option<super::TranscodeError> = $err;
+                assert_eq!($dst, &output[..written]);
+                assert_eq!(err.is_some(), has_error);
+                assert_eq!(err.as_ref(), t.error());
+            }
+        };
+    }
+
+    use super::TranscodeError::*;
+    strict_test!(strict_valid_sjis_utf8      ,  "sjis" ,    "utf-8" , b"a\x84\x47"         , b"a\xD0\x96"     , None); // aЖ
+    strict_test!(strict_valid_sjis_eucjp     ,  "sjis" ,   "euc-jp" , b"a\x84\x47"         , b"a\xA7\xA8"     , None); // aЖ
+    strict_test!(strict_malformed_sjis_utf8  ,  "sjis" ,    "utf-8" , b"a\x84\x47\xFFb"    , b"a\xD0\x96"     , Some(Malformed { offset: 3 }));
+    strict_test!(strict_malformed_sjis_utf16 ,  "sjis" , "utf-16le" , b"a\x84\x47\xFFb"    , b"a\x00\x16\x04" , Some(Malformed { offset: 3 }));
+    strict_test!(strict_malformed_sjis_eucjp ,  "sjis" ,   "euc-jp" , b"a\x84\x47\xFFb"    , b"a\xA7\xA8"     , Some(Malformed { offset: 3 }));
+    strict_test!(strict_truncated_sjis_eucjp ,  "sjis" ,   "euc-jp" , b"a\x84"             , b"a"             , Some(Malformed { offset: 1 }));
+    strict_test!(strict_unmappable_utf8_sjis , "utf-8" ,     "sjis" , b"a\xC3\xA9b"         , b"a"             , Some(Unmappable { offset: 1, character: 'é' }));
+    strict_test!(strict_unmappable_sjis_latin1, "sjis" ,   "latin1" , b"ab\x84\x47"         , b"ab"            , Some(Unmappable { offset: 2, character: 'Ж' }));
+    strict_test!(strict_malformed_utf32_utf8 , "utf-32",    "utf-8" , b"a\x00\x00\x00\x00\xD8\x00\x00", b"a"         , Some(Malformed { offset: 4 }));
+    strict_test!(strict_truncated_utf8_utf32 , "utf-8" , "utf-32be" , b"a\xD0"             , b"\x00\x00\x00a" , Some(Malformed { offset: 1 }));
+
+    macro_rules! unmappable_test {
+        ($name:ident, $policy:expr, $enc:expr, $src:expr, $dst:expr) => {
+            #[test]
+            fn $name() {
+                let enc = super::enc::Encoding::for_label($enc.as_bytes());
+                let mut t = super::Transcoder::new(Some(super::enc::UTF_8), enc.unwrap()).unmappable($policy);
+                let output = &mut [0u8; 140];
+                let (_,_,written,has_unmappable) = t.transcode($src.as_bytes(), output, true);
+                assert_eq!($dst, &output[..written]);
+                assert!(has_unmappable);
+            }
+        };
+    }
+
+    use super::UnmappablePolicy;
+    unmappable_test!(unmappable_ncr           , UnmappablePolicy::Ncr             ,        "sjis" , "aé😀b" , b"a&#233;&#128512;b");
+    unmappable_test!(unmappable_substitute    , UnmappablePolicy::Substitute('?') ,        "sjis" , "aé😀b" , b"a??b");
+    unmappable_test!(unmappable_substitute_unmappable, UnmappablePolicy::Substitute('é'), "sjis" , "aé😀b" , b"ab");
+    unmappable_test!(unmappable_skip          , UnmappablePolicy::Skip            ,        "sjis" , "aé😀b" , b"ab");
+    unmappable_test!(unmappable_escape        , UnmappablePolicy::Escape          ,        "sjis" , "aé😀b" , b"a\\u00E9\\uD83D\\uDE00b");
+    unmappable_test!(unmappable_transliterate , UnmappablePolicy::Transliterate   , "windows-1252", "a€Жb" , b"a\x80Zhb");
+    unmappable_test!(unmappable_iso2022jp     , UnmappablePolicy::Substitute('?') , "iso-2022-jp" , "ハéロ" , b"\x1B$B%O\x1B(B?\x1B$B%m\x1B(B");
+
+    #[test]
+    fn unmappable_error() {
+        let mut t = super::Transcoder::new(Some(super::enc::UTF_8), super::enc::SHIFT_JIS).unmappable(UnmappablePolicy::Error);
+        let output = &mut [0u8; 140];
+        let (_,_,written,has_error) = t.transcode(b"ab\xC3\xA9c", output, true);
+        assert!(has_error);
+        assert_eq!(b"ab", &output[..written]);
+        assert_eq!(Some(&Unmappable { offset: 2, character: 'é' }), t.error());
+    }
+
+    #[test]
+    fn unmappable_output_full() {
+        let mut t = super::Transcoder::new(Some(super::enc::UTF_8), super::enc::SHIFT_JIS).unmappable(UnmappablePolicy::Escape);
+        let input = "é".repeat(10);
+        let output = &mut [0u8; 16];
+        let mut result = Vec::new();
+        let mut num_read = 0;
+        loop {
+            let (coder_result,read,written,_) = t.transcode(&input.as_bytes()[num_read..], output, true);
+            num_read += read;
+            result.extend_from_slice(&output[..written]);
+            if coder_result == super::enc::CoderResult::InputEmpty {
+                break;
+            }
+        }
+        assert_eq!("\\u00E9".repeat(10).as_bytes(), &result[..]);
+    }
+
+    #[test]
+    fn unmappable_error_small_output() {
+        let mut t = super::Transcoder::new(Some(super::enc::UTF_8), super::enc::ISO_2022_JP).unmappable(UnmappablePolicy::Error);
+        let input = "aハロb".as_bytes();
+        let output = &mut [0u8; 3];
+        let mut result = Vec::new();
+        let mut num_read = 0;
+        loop {
+            let (coder_result,read,written,has_error) = t.transcode(&input[num_read..], output, true);
+            assert!(!has_error);
+            num_read += read;
+            result.extend_from_slice(&output[..written]);
+            if coder_result == super::enc::CoderResult::InputEmpty {
+                break;
+            }
+        }
+        assert_eq!(b"a\x1B$B%O%m\x1B(Bb", &result[..]);
+        assert_eq!(input.len(), num_read);
+    }
+
+    #[test]
+    fn unmappable_error_unicode() {
+        let mut t = super::Transcoder::new(Some(super::enc::SHIFT_JIS), super::enc::UTF_8).unmappable(UnmappablePolicy::Error);
+        let output = &mut [0u8; 8];
+        let (coder_result,read,written,has_error) = t.transcode(b"a\x83\x6E", output, true);
+        assert_eq!(super::enc::CoderResult::InputEmpty, coder_result);
+        assert_eq!((3, false), (read, has_error));
+        assert_eq!("aハ".as_bytes(), &output[..written]);
+        assert_eq!(None, t.error());
+    }
 }